@@ -0,0 +1,102 @@
+//! Conditional request handling, using `ETag` and `Last-Modified` validators.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use axum::http::{header, HeaderMap, HeaderValue};
+
+/// HTTP dates only have second precision, so anything finer breaks comparisons.
+fn truncate_to_secs(time: SystemTime) -> SystemTime {
+    let secs = time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+/// The validators describing a single version of a resource.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Validators {
+    pub etag: String,
+    pub last_modified: SystemTime,
+}
+
+impl Validators {
+    /// Create validators from a resource length and modification time.
+    pub fn new(len: u64, modified: SystemTime) -> Validators {
+        let last_modified = truncate_to_secs(modified);
+        let secs = last_modified.duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        Validators {
+            etag: format!("\"{:x}-{:x}\"", len, secs),
+            last_modified,
+        }
+    }
+
+    /// Create validators for an in-memory resource, using a hash of its contents.
+    pub fn for_contents(contents: &[u8], modified: SystemTime) -> Validators {
+        let mut hasher = DefaultHasher::new();
+        contents.hash(&mut hasher);
+        Validators {
+            etag: format!("\"{:x}-{:016x}\"", contents.len(), hasher.finish()),
+            last_modified: truncate_to_secs(modified),
+        }
+    }
+
+    fn etag_matches(&self, if_none_match: &str) -> bool {
+        if_none_match.split(',')
+            .map(|tag| tag.trim())
+            .map(|tag| tag.strip_prefix("W/").unwrap_or(tag))
+            .any(|tag| tag == "*" || tag == self.etag)
+    }
+
+    /// Check whether the client already holds this version of the resource.
+    ///
+    /// As per RFC 7232, `If-Modified-Since` is ignored when `If-None-Match` is present.
+    pub fn is_not_modified(&self, headers: &HeaderMap) -> bool {
+        if let Some(value) = headers.get(header::IF_NONE_MATCH) {
+            return value.to_str().is_ok_and(|v| self.etag_matches(v));
+        }
+
+        headers.get(header::IF_MODIFIED_SINCE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| httpdate::parse_http_date(v).ok())
+            .is_some_and(|since| self.last_modified <= since)
+    }
+
+    /// Add the validator headers to a response.
+    pub fn apply(&self, headers: &mut HeaderMap) {
+        if let Ok(etag) = HeaderValue::from_str(&self.etag) {
+            headers.insert(header::ETAG, etag);
+        }
+        if let Ok(date) = HeaderValue::from_str(&httpdate::fmt_http_date(self.last_modified)) {
+            headers.insert(header::LAST_MODIFIED, date);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_not_modified() {
+        let modified = UNIX_EPOCH + Duration::from_millis(1_600_000_000_500);
+        let validators = Validators::new(1234, modified);
+
+        let mut headers = HeaderMap::new();
+        assert!(!validators.is_not_modified(&headers));
+
+        headers.insert(header::IF_MODIFIED_SINCE,
+                       HeaderValue::from_str(&httpdate::fmt_http_date(modified)).unwrap());
+        assert!(validators.is_not_modified(&headers));
+
+        headers.insert(header::IF_NONE_MATCH, HeaderValue::from_static("\"other\""));
+        assert!(!validators.is_not_modified(&headers));
+
+        let etag = format!("\"other\", W/{}", validators.etag);
+        headers.insert(header::IF_NONE_MATCH, HeaderValue::from_str(&etag).unwrap());
+        assert!(validators.is_not_modified(&headers));
+    }
+}
@@ -0,0 +1,295 @@
+//! Recent positions of each pilot, so that the viewer can draw trails and
+//! altitude profiles.
+//!
+//! The history of each callsign is split into sessions, with a new session
+//! started whenever the pilot reconnects or jumps further than any aircraft
+//! could have flown since the last update.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use axum::response::{IntoResponse, Response};
+use serde::Serialize;
+
+use ivao::whazzup::{Track, Whazzup};
+use open_air::domain::coords::distance_nm;
+
+use crate::recording::to_millis;
+use crate::whazzup::WhazzupCache;
+
+/// The number of sessions kept for each callsign, including the current one.
+const MAX_SESSIONS: usize = 3;
+
+/// The fastest ground speed considered plausible, in knots.
+const MAX_GROUND_SPEED: f64 = 1500.;
+
+/// How long to keep the history of callsigns which are no longer connected.
+const RETENTION: Duration = Duration::from_secs(60 * 60);
+
+/// A single reported position.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackPoint {
+    /// The time of the snapshot containing this position, in milliseconds since the Unix epoch.
+    pub time: u64,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub map_x: f64,
+    pub map_y: f64,
+    pub altitude: f64,
+    pub ground_speed: f64,
+    pub heading: f64,
+    pub on_ground: bool,
+}
+
+impl TrackPoint {
+    fn new(time: u64, track: &Track) -> TrackPoint {
+        TrackPoint {
+            time,
+            latitude: track.latitude,
+            longitude: track.longitude,
+            map_x: track.map_x,
+            map_y: track.map_y,
+            altitude: track.altitude,
+            ground_speed: track.ground_speed,
+            heading: track.heading,
+            on_ground: track.on_ground,
+        }
+    }
+
+    fn position(&self) -> (f64, f64) {
+        (self.latitude, self.longitude)
+    }
+
+    fn same_report(&self, other: &TrackPoint) -> bool {
+        self.position() == other.position()
+            && self.altitude == other.altitude
+            && self.heading == other.heading
+    }
+
+    /// Whether an aircraft could plausibly have flown from this position to another.
+    fn can_reach(&self, other: &TrackPoint) -> bool {
+        // Allow at least a minute, as the feed can deliver updates in bursts.
+        let hours = (other.time.saturating_sub(self.time) as f64 / 3_600_000.).max(1. / 60.);
+        distance_nm(self.position(), other.position()) <= MAX_GROUND_SPEED * hours
+    }
+}
+
+/// A continuous period during which a callsign was connected.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Session {
+    /// The whazzup session id of the pilot.
+    pub id: u64,
+    pub points: VecDeque<TrackPoint>,
+}
+
+#[derive(Debug, Default)]
+struct CallsignHistory {
+    sessions: VecDeque<Session>,
+    last_seen: u64,
+}
+
+impl CallsignHistory {
+    fn push(&mut self, id: u64, point: TrackPoint, length: usize) {
+        let session = match self.sessions.back_mut() {
+            Some(session) if session.id == id => session,
+            _ => self.start_session(id),
+        };
+
+        match session.points.back() {
+            Some(last) if last.same_report(&point) => return,
+            Some(last) if !last.can_reach(&point) => {}
+            _ => {
+                session.points.push_back(point);
+                while session.points.len() > length {
+                    session.points.pop_front();
+                }
+                return;
+            }
+        }
+
+        self.start_session(id).points.push_back(point);
+    }
+
+    fn start_session(&mut self, id: u64) -> &mut Session {
+        if self.sessions.len() >= MAX_SESSIONS {
+            self.sessions.pop_front();
+        }
+        self.sessions.push_back(Session {
+            id,
+            points: VecDeque::new(),
+        });
+        self.sessions.back_mut().unwrap()
+    }
+}
+
+/// The recent positions of every pilot.
+#[derive(Debug)]
+pub struct History {
+    callsigns: HashMap<String, CallsignHistory>,
+    session_length: usize,
+    latest: u64,
+}
+
+impl History {
+    pub fn new(session_length: usize) -> History {
+        History {
+            callsigns: HashMap::new(),
+            session_length: session_length.max(1),
+            latest: 0,
+        }
+    }
+
+    /// Add the positions from a snapshot taken at the given time.
+    pub fn update(&mut self, time: SystemTime, whazzup: &Whazzup) {
+        let time = to_millis(time);
+
+        // Time only goes backwards when seeking during a replay, in which case
+        // the history we have no longer leads up to the current snapshot.
+        if time < self.latest {
+            self.callsigns.clear();
+        }
+        self.latest = time;
+
+        for pilot in &whazzup.clients.pilots {
+            let history = self.callsigns.entry(pilot.callsign.clone()).or_default();
+            history.last_seen = time;
+            if let Some(track) = &pilot.last_track {
+                history.push(pilot.id, TrackPoint::new(time, track), self.session_length);
+            }
+        }
+
+        let cutoff = time.saturating_sub(RETENTION.as_millis() as u64);
+        self.callsigns.retain(|_, h| h.last_seen >= cutoff);
+    }
+
+    /// Get the sessions of a callsign, oldest first.
+    pub fn sessions(&self, callsign: &str) -> Option<&VecDeque<Session>> {
+        self.callsigns.get(callsign).map(|h| &h.sessions)
+    }
+}
+
+/// Shared access to the history, updated as new snapshots arrive.
+#[derive(Clone)]
+pub struct TrackHistory {
+    history: Arc<RwLock<History>>,
+}
+
+impl TrackHistory {
+    pub fn new(session_length: usize) -> TrackHistory {
+        TrackHistory {
+            history: Arc::new(RwLock::new(History::new(session_length))),
+        }
+    }
+
+    /// Add every snapshot which passes through the cache to the history.
+    pub async fn follow(self, cache: WhazzupCache) {
+        let mut receiver = cache.subscribe();
+        receiver.mark_changed();
+        while receiver.changed().await.is_ok() {
+            let snapshot = receiver.borrow_and_update().clone();
+            if let Some(snapshot) = snapshot {
+                self.history.write().unwrap()
+                    .update(snapshot.fetched_at, &snapshot.whazzup);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryResponse {
+    pub callsign: String,
+    pub sessions: VecDeque<Session>,
+}
+
+pub async fn get_history(State(history): State<TrackHistory>, Path(callsign): Path<String>) -> Response {
+    let history = history.history.read().unwrap();
+    match history.sessions(&callsign) {
+        Some(sessions) => Json(HistoryResponse {
+            callsign,
+            sessions: sessions.clone(),
+        }).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::UNIX_EPOCH;
+
+    use super::*;
+
+    const FIXTURE: &[u8] = include_bytes!("../../ivao/fixtures/whazzup_v2.json");
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    fn move_pilot(whazzup: &mut Whazzup, latitude: f64, longitude: f64) {
+        let track = whazzup.clients.pilots[0].last_track.as_mut().unwrap();
+        track.latitude = latitude;
+        track.longitude = longitude;
+        track.project();
+    }
+
+    #[test]
+    fn test_history() {
+        let mut whazzup = Whazzup::parse(FIXTURE).unwrap();
+        let mut history = History::new(3);
+
+        history.update(at(0), &whazzup);
+        history.update(at(15), &whazzup);
+        for (idx, longitude) in [-8.2, -8.3, -8.4].iter().enumerate() {
+            move_pilot(&mut whazzup, 52.4, *longitude);
+            history.update(at(30 + 15 * idx as u64), &whazzup);
+        }
+
+        let sessions = history.sessions("BAW123").unwrap();
+        assert_eq!(sessions.len(), 1);
+        let longitudes = sessions[0].points.iter().map(|p| p.longitude).collect::<Vec<_>>();
+        assert_eq!(longitudes, vec![-8.2, -8.3, -8.4]);
+
+        // DLH4AB has no track, but is still known.
+        assert!(history.sessions("DLH4AB").unwrap().is_empty());
+        assert!(history.sessions("EGLL_TWR").is_none());
+    }
+
+    #[test]
+    fn test_split_sessions() {
+        let mut whazzup = Whazzup::parse(FIXTURE).unwrap();
+        let mut history = History::new(100);
+        history.update(at(0), &whazzup);
+
+        // Across the Atlantic in 15 seconds.
+        move_pilot(&mut whazzup, 40.6, -73.8);
+        history.update(at(15), &whazzup);
+
+        whazzup.clients.pilots[0].id += 1;
+        history.update(at(30), &whazzup);
+
+        let sessions = history.sessions("BAW123").unwrap();
+        assert_eq!(sessions.len(), 3);
+        assert_eq!(sessions[0].id, sessions[1].id);
+        assert_ne!(sessions[1].id, sessions[2].id);
+        assert!(sessions.iter().all(|s| s.points.len() == 1));
+    }
+
+    #[test]
+    fn test_expiry() {
+        let whazzup = Whazzup::parse(FIXTURE).unwrap();
+        let mut history = History::new(100);
+        history.update(at(0), &whazzup);
+        history.update(at(RETENTION.as_secs() + 1), &Whazzup::default());
+        assert!(history.sessions("BAW123").is_none());
+
+        history.update(at(100), &whazzup);
+        history.update(at(50), &whazzup);
+        assert_eq!(history.sessions("BAW123").unwrap()[0].points[0].time, 50_000);
+    }
+}
@@ -0,0 +1,148 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use axum::http::{header, HeaderValue, Method};
+use axum::Router;
+use axum::routing::get;
+use clap::Clap;
+use tower_http::compression::CompressionLayer;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+use history::TrackHistory;
+use recording::{Recorder, Recording, Replay};
+use tiles::TileStore;
+use whazzup::{WhazzupCache, WhazzupSource};
+
+mod caching;
+mod history;
+mod recording;
+mod tiles;
+mod traffic;
+mod whazzup;
+
+#[derive(Clap)]
+struct Opts {
+    /// The directory containing the output of `convert_sectors`.
+    #[clap(short, long)]
+    data_dir: PathBuf,
+
+    #[clap(short, long, default_value = "127.0.0.1:8080")]
+    bind: String,
+
+    /// An origin allowed to make cross-origin requests, or `*` to allow any.
+    #[clap(long)]
+    cors_origin: Vec<String>,
+
+    /// The URL or local path of a whazzup v2 feed to serve at `/whazzup` and `/traffic`.
+    #[clap(long)]
+    whazzup_source: Option<WhazzupSource>,
+
+    /// How often to poll the whazzup source, in seconds.
+    #[clap(long, default_value = "15")]
+    whazzup_interval: u64,
+
+    /// How many positions to keep for each pilot session at `/history/{callsign}`.
+    #[clap(long, default_value = "720")]
+    history_length: usize,
+
+    /// Append every whazzup snapshot to a recording.
+    #[clap(long, requires = "whazzup-source")]
+    record: Option<PathBuf>,
+
+    /// Serve a recording instead of polling a whazzup source.
+    ///
+    /// Playback can be controlled at `/replay`.
+    #[clap(long, conflicts_with = "whazzup-source")]
+    replay: Option<PathBuf>,
+
+    /// How fast to play back the recording, relative to real time.
+    #[clap(long, default_value = "1")]
+    replay_speed: f64,
+}
+
+fn cors_layer(origins: &[String]) -> anyhow::Result<CorsLayer> {
+    let allow_origin = if origins.iter().any(|o| o == "*") {
+        AllowOrigin::any()
+    } else {
+        let origins = origins.iter()
+            .map(|o| HeaderValue::from_str(o).map_err(|_| anyhow!("invalid CORS origin: {}", o)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        AllowOrigin::list(origins)
+    };
+
+    Ok(CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods([Method::GET, Method::HEAD, Method::POST])
+        .allow_headers([header::CONTENT_TYPE, header::IF_NONE_MATCH, header::IF_MODIFIED_SINCE])
+        .expose_headers([header::ETAG, header::LAST_MODIFIED]))
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    env_logger::init();
+
+    let opts = Opts::parse();
+    if !opts.data_dir.is_dir() {
+        return Err(anyhow!("data directory {:?} does not exist", opts.data_dir));
+    }
+
+    // Traffic is projected the same way as the tiles, so it lines up with the map.
+    let projection = tiles::load_projection(&opts.data_dir)
+        .map_err(|err| anyhow!("failed to read projection from {:?}: {}", opts.data_dir, err))?;
+    log::info!("projecting traffic with {:?}", projection);
+
+    let tiles = Arc::new(TileStore::new(opts.data_dir));
+    let mut app = Router::new()
+        .route("/{name}", get(tiles::get_tile))
+        .with_state(tiles);
+
+    let cache = WhazzupCache::with_projection(projection);
+    let mut serve_traffic = false;
+
+    if let Some(source) = opts.whazzup_source {
+        let interval = Duration::from_secs(opts.whazzup_interval.max(1));
+        tokio::spawn(whazzup::poll(source, interval, cache.clone()));
+
+        if let Some(path) = opts.record {
+            let recorder = Recorder::create(&path)
+                .map_err(|err| anyhow!("failed to open recording {:?}: {}", path, err))?;
+            tokio::spawn(recording::record(recorder, cache.clone()));
+        }
+
+        serve_traffic = true;
+    } else if let Some(path) = opts.replay {
+        let recording = Recording::load(&path)
+            .map_err(|err| anyhow!("failed to load recording {:?}: {}", path, err))?;
+        let replay = Replay::new(recording, opts.replay_speed)?;
+        tokio::spawn(replay.clone().run(cache.clone()));
+
+        app = app.merge(Router::new()
+            .route("/replay", get(recording::get_replay).post(recording::post_replay))
+            .with_state(replay));
+        serve_traffic = true;
+    }
+
+    if serve_traffic {
+        let history = TrackHistory::new(opts.history_length);
+        tokio::spawn(history.clone().follow(cache.clone()));
+
+        app = app.merge(Router::new()
+            .route("/history/{callsign}", get(history::get_history))
+            .with_state(history));
+        app = app.merge(Router::new()
+            .route("/whazzup", get(whazzup::get_whazzup))
+            .route("/traffic", get(traffic::get_traffic))
+            .with_state(cache));
+    }
+
+    let app = app
+        .layer(cors_layer(&opts.cors_origin)?)
+        .layer(CompressionLayer::new());
+
+    let listener = tokio::net::TcpListener::bind(&opts.bind).await?;
+    log::info!("listening on {}", listener.local_addr()?);
+    axum::serve(listener, app).await?;
+    Ok(())
+}
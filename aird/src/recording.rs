@@ -0,0 +1,655 @@
+//! Recording the traffic picture to disk, and replaying it later.
+//!
+//! A recording is a file of JSON lines, one record per snapshot. Most records
+//! only contain the changes since the previous one, with a complete snapshot
+//! written every so often so that seeking doesn't need to start from the
+//! beginning of the file.
+//!
+//! Each time the recorder starts, it appends a new segment beginning with a
+//! complete snapshot, so a recording survives the recorder being interrupted
+//! and restarted.
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::anyhow;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use axum::response::{IntoResponse, Response};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+
+use ivao::whazzup::{Observer, Whazzup};
+
+use crate::traffic::{self, TrafficEvent};
+use crate::whazzup::WhazzupCache;
+
+/// How many records to write between complete snapshots.
+pub const DEFAULT_KEYFRAME_INTERVAL: usize = 20;
+
+/// Convert a time to milliseconds since the Unix epoch.
+pub fn to_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+fn from_millis(millis: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(millis)
+}
+
+/// A single line of a recording. Times are in milliseconds since the Unix epoch.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Record {
+    /// The complete state of the network.
+    #[serde(rename_all = "camelCase")]
+    Snapshot { time: u64, whazzup: Whazzup },
+    /// The changes since the previous record.
+    #[serde(rename_all = "camelCase")]
+    Diff {
+        time: u64,
+        updated_at: String,
+        events: Vec<TrafficEvent>,
+        /// Observers aren't part of the traffic events, so they are only
+        /// included when they change.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        observers: Option<Vec<Observer>>,
+    },
+}
+
+impl Record {
+    pub fn time(&self) -> u64 {
+        match self {
+            Record::Snapshot { time, .. } | Record::Diff { time, .. } => *time,
+        }
+    }
+
+    /// Update the state of the network with this record.
+    fn apply(&self, state: &mut Whazzup) {
+        match self {
+            Record::Snapshot { whazzup, .. } => *state = whazzup.clone(),
+            Record::Diff { updated_at, events, observers, .. } => {
+                state.updated_at = updated_at.clone();
+                traffic::apply(state, events);
+                if let Some(observers) = observers {
+                    state.clients.observers = observers.clone();
+                }
+            }
+        }
+    }
+}
+
+/// Writes snapshots to a recording.
+pub struct Recorder<W> {
+    writer: W,
+    previous: Option<Whazzup>,
+    keyframe_interval: usize,
+    since_keyframe: usize,
+}
+
+impl Recorder<BufWriter<File>> {
+    /// Open a recording file, appending to it if it already exists.
+    ///
+    /// If the file ends part way through a line, because the recorder was
+    /// interrupted, the line is ended so that the new records start on a line
+    /// of their own.
+    pub fn create(path: impl AsRef<Path>) -> anyhow::Result<Recorder<BufWriter<File>>> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path)?;
+
+        if file.seek(SeekFrom::End(0))? > 0 {
+            let mut last = [0u8];
+            file.seek(SeekFrom::End(-1))?;
+            file.read_exact(&mut last)?;
+            if last[0] != b'\n' {
+                warn!("recording ends with a partial record, starting a new line");
+                file.write_all(b"\n")?;
+            }
+        }
+
+        Ok(Recorder::new(BufWriter::new(file), DEFAULT_KEYFRAME_INTERVAL))
+    }
+}
+
+impl<W: Write> Recorder<W> {
+    pub fn new(writer: W, keyframe_interval: usize) -> Recorder<W> {
+        Recorder {
+            writer,
+            previous: None,
+            keyframe_interval: keyframe_interval.max(1),
+            since_keyframe: 0,
+        }
+    }
+
+    /// Append a snapshot to the recording.
+    ///
+    /// The first snapshot written is always complete, so that a recording which
+    /// has been appended to several times can still be replayed.
+    pub fn record(&mut self, time: SystemTime, whazzup: &Whazzup) -> anyhow::Result<()> {
+        let time = to_millis(time);
+        let record = match &self.previous {
+            Some(previous) if self.since_keyframe < self.keyframe_interval => {
+                let observers = &whazzup.clients.observers;
+                Record::Diff {
+                    time,
+                    updated_at: whazzup.updated_at.clone(),
+                    events: traffic::diff(previous, whazzup),
+                    observers: (*observers != previous.clients.observers).then(|| observers.clone()),
+                }
+            }
+            _ => Record::Snapshot { time, whazzup: whazzup.clone() },
+        };
+
+        match record {
+            Record::Snapshot { .. } => self.since_keyframe = 0,
+            Record::Diff { .. } => self.since_keyframe += 1,
+        }
+
+        serde_json::to_writer(&mut self.writer, &record)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()?;
+        self.previous = Some(whazzup.clone());
+        Ok(())
+    }
+}
+
+/// Record every snapshot which passes through the cache.
+pub async fn record<W: Write>(mut recorder: Recorder<W>, cache: WhazzupCache) {
+    let mut receiver = cache.subscribe();
+    // Include the snapshot which was current when recording started.
+    receiver.mark_changed();
+    while receiver.changed().await.is_ok() {
+        let snapshot = receiver.borrow_and_update().clone();
+        if let Some(snapshot) = snapshot {
+            if let Err(err) = recorder.record(snapshot.fetched_at, &snapshot.whazzup) {
+                warn!("failed to record whazzup snapshot: {}", err);
+            }
+        }
+    }
+}
+
+/// A recording loaded into memory.
+#[derive(Debug)]
+pub struct Recording {
+    records: Vec<Record>,
+}
+
+impl Recording {
+    /// Load a recording from a file.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Recording> {
+        Recording::parse(BufReader::new(File::open(path)?))
+    }
+
+    /// Parse a recording.
+    ///
+    /// A record which can't be read is skipped if it is the last in the file,
+    /// or if a complete snapshot follows it, as left behind if the recorder was
+    /// interrupted. A snapshot which goes back in time starts a new segment,
+    /// which replaces the overlapping end of the earlier records.
+    pub fn parse(reader: impl BufRead) -> anyhow::Result<Recording> {
+        let lines = reader.lines()
+            .enumerate()
+            .map(|(idx, line)| Ok((idx, line?)))
+            .filter(|line| !matches!(line, Ok((_, line)) if line.trim().is_empty()))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        let mut records: Vec<Record> = Vec::new();
+
+        for (pos, (idx, line)) in lines.iter().enumerate() {
+            let record = match serde_json::from_str::<Record>(line) {
+                Ok(record) => record,
+                Err(err) => {
+                    let next = lines.get(pos + 1)
+                        .map(|(_, line)| serde_json::from_str::<Record>(line));
+                    match next {
+                        None => warn!("ignoring truncated record on line {}: {}", idx + 1, err),
+                        Some(Ok(Record::Snapshot { .. })) =>
+                            warn!("ignoring interrupted record on line {}: {}", idx + 1, err),
+                        Some(_) => return Err(anyhow!("invalid record on line {}: {}", idx + 1, err)),
+                    }
+                    continue;
+                }
+            };
+
+            if records.last().is_some_and(|r| r.time() > record.time()) {
+                if !matches!(record, Record::Snapshot { .. }) {
+                    return Err(anyhow!("record on line {} is out of order", idx + 1));
+                }
+                let overlap = records.partition_point(|r| r.time() < record.time());
+                warn!("record on line {} goes back in time, replacing {} earlier record(s)",
+                      idx + 1, records.len() - overlap);
+                records.truncate(overlap);
+            }
+            records.push(record);
+        }
+
+        match records.first() {
+            Some(Record::Snapshot { .. }) => Ok(Recording { records }),
+            Some(Record::Diff { .. }) => Err(anyhow!("recording doesn't start with a snapshot")),
+            None => Err(anyhow!("recording is empty")),
+        }
+    }
+
+    /// The time of the first record.
+    pub fn start(&self) -> u64 {
+        self.records[0].time()
+    }
+
+    /// The time of the last record.
+    pub fn end(&self) -> u64 {
+        self.records[self.records.len() - 1].time()
+    }
+
+    /// Find the index of the record in effect at a given time.
+    pub fn index_at(&self, time: u64) -> usize {
+        self.records.partition_point(|r| r.time() <= time)
+            .saturating_sub(1)
+    }
+
+    /// Reconstruct the state of the network after the record at `index`.
+    pub fn state_at(&self, index: usize) -> Whazzup {
+        let keyframe = self.records[..=index].iter()
+            .rposition(|r| matches!(r, Record::Snapshot { .. }))
+            .unwrap_or(0);
+
+        let mut state = Whazzup::default();
+        self.advance(&mut state, keyframe, index);
+        state
+    }
+
+    /// Apply the records from `from` to `to` inclusive.
+    fn advance(&self, state: &mut Whazzup, from: usize, to: usize) {
+        for record in &self.records[from..=to] {
+            record.apply(state);
+        }
+    }
+}
+
+/// The position and speed of playback.
+#[derive(Debug, Clone, Copy)]
+struct Clock {
+    position: u64,
+    since: Instant,
+    speed: f64,
+    paused: bool,
+}
+
+impl Clock {
+    fn position(&self) -> u64 {
+        if self.paused {
+            return self.position;
+        }
+
+        let elapsed = self.since.elapsed().as_secs_f64() * self.speed;
+        self.position + (elapsed * 1000.) as u64
+    }
+
+    /// Move the clock, keeping the current position unless told otherwise.
+    fn reset(&mut self, position: Option<u64>) {
+        self.position = position.unwrap_or_else(|| self.position());
+        self.since = Instant::now();
+    }
+}
+
+/// The state of playback, as reported to viewers.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplayStatus {
+    pub start: u64,
+    pub end: u64,
+    pub position: u64,
+    pub speed: f64,
+    pub paused: bool,
+}
+
+/// A change to playback, requested by a viewer.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ReplayControl {
+    /// The time to jump to, in milliseconds since the Unix epoch.
+    pub seek: Option<u64>,
+    pub speed: Option<f64>,
+    pub paused: Option<bool>,
+}
+
+fn check_speed(speed: f64) -> anyhow::Result<f64> {
+    if speed.is_finite() && speed > 0. {
+        Ok(speed)
+    } else {
+        Err(anyhow!("invalid replay speed: {}", speed))
+    }
+}
+
+/// Plays a recording back into the whazzup cache.
+#[derive(Clone)]
+pub struct Replay {
+    recording: Arc<Recording>,
+    clock: Arc<watch::Sender<Clock>>,
+}
+
+impl Replay {
+    pub fn new(recording: Recording, speed: f64) -> anyhow::Result<Replay> {
+        let clock = Clock {
+            position: recording.start(),
+            since: Instant::now(),
+            speed: check_speed(speed)?,
+            paused: false,
+        };
+
+        Ok(Replay {
+            recording: Arc::new(recording),
+            clock: Arc::new(watch::channel(clock).0),
+        })
+    }
+
+    pub fn status(&self) -> ReplayStatus {
+        let clock = *self.clock.borrow();
+        ReplayStatus {
+            start: self.recording.start(),
+            end: self.recording.end(),
+            position: clock.position().min(self.recording.end()),
+            speed: clock.speed,
+            paused: clock.paused,
+        }
+    }
+
+    /// Seek, pause, or change the speed of playback.
+    pub fn control(&self, control: &ReplayControl) -> anyhow::Result<()> {
+        let speed = control.speed.map(check_speed).transpose()?;
+        let seek = control.seek
+            .map(|t| t.clamp(self.recording.start(), self.recording.end()));
+
+        self.clock.send_modify(|clock| {
+            clock.reset(seek);
+            if let Some(speed) = speed {
+                clock.speed = speed;
+            }
+            if let Some(paused) = control.paused {
+                clock.paused = paused;
+            }
+        });
+        Ok(())
+    }
+
+    /// Play the recording forever, following any changes to the clock.
+    pub async fn run(self, cache: WhazzupCache) {
+        let mut clock = self.clock.subscribe();
+        let mut current: Option<(usize, Whazzup)> = None;
+
+        loop {
+            let now = *clock.borrow_and_update();
+            let position = now.position();
+            let index = self.recording.index_at(position);
+
+            // Playing forwards only needs the records since the last update,
+            // but anything else has to start from the nearest snapshot.
+            let state = match current.take() {
+                Some((prev, mut state)) if prev < index => {
+                    self.recording.advance(&mut state, prev + 1, index);
+                    state
+                }
+                Some((prev, state)) if prev == index => state,
+                _ => self.recording.state_at(index),
+            };
+
+            let time = from_millis(self.recording.records[index].time());
+            if let Err(err) = cache.update(state.clone(), time) {
+                warn!("failed to replay snapshot: {}", err);
+            }
+            current = Some((index, state));
+
+            let next = self.recording.records.get(index + 1).map(Record::time);
+            let wait = match next {
+                Some(next) if !now.paused => {
+                    let millis = next.saturating_sub(position) as f64 / now.speed;
+                    Some(Duration::from_secs_f64(millis / 1000.))
+                }
+                _ => None,
+            };
+
+            let changed = match wait {
+                Some(wait) => tokio::select! {
+                    _ = tokio::time::sleep(wait) => Ok(()),
+                    changed = clock.changed() => changed,
+                },
+                None => {
+                    if next.is_none() {
+                        info!("reached the end of the recording");
+                    }
+                    clock.changed().await
+                }
+            };
+
+            if changed.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+pub async fn get_replay(State(replay): State<Replay>) -> Json<ReplayStatus> {
+    Json(replay.status())
+}
+
+pub async fn post_replay(State(replay): State<Replay>, Json(control): Json<ReplayControl>) -> Response {
+    match replay.control(&control) {
+        Ok(()) => Json(replay.status()).into_response(),
+        Err(err) => (StatusCode::BAD_REQUEST, err.to_string()).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &[u8] = include_bytes!("../../ivao/fixtures/whazzup_v2.json");
+
+    /// A few snapshots in which BAW123 flies west, DLH4AB is upgraded, and
+    /// EGLL_TWR logs off.
+    fn timeline() -> Vec<Whazzup> {
+        let first = Whazzup::parse(FIXTURE).unwrap();
+        let mut snapshots = vec![first];
+        for step in 1..5 {
+            let mut next = snapshots[step - 1].clone();
+            let track = next.clients.pilots[0].last_track.as_mut().unwrap();
+            track.longitude -= 0.5;
+            track.project();
+            for pilot in next.clients.pilots.iter_mut() {
+                pilot.time += 15;
+            }
+            if step == 2 {
+                next.clients.pilots[1].rating += 1;
+            }
+            next.updated_at = format!("step {}", step);
+            if step == 3 {
+                next.clients.atcs.clear();
+                next.clients.observers.clear();
+            }
+            snapshots.push(next);
+        }
+        snapshots
+    }
+
+    fn record(snapshots: &[Whazzup], keyframe_interval: usize) -> Recording {
+        let mut contents = Vec::new();
+        let mut recorder = Recorder::new(&mut contents, keyframe_interval);
+        for (idx, snapshot) in snapshots.iter().enumerate() {
+            recorder.record(from_millis(1000 * idx as u64), snapshot).unwrap();
+        }
+        Recording::parse(contents.as_slice()).unwrap()
+    }
+
+    #[test]
+    fn test_record() {
+        let snapshots = timeline();
+        let recording = record(&snapshots, 2);
+
+        let kinds = recording.records.iter()
+            .map(|r| matches!(r, Record::Snapshot { .. }))
+            .collect::<Vec<_>>();
+        assert_eq!(kinds, vec![true, false, false, true, false]);
+        assert_eq!(recording.start(), 0);
+        assert_eq!(recording.end(), 4000);
+
+        for (idx, snapshot) in snapshots.iter().enumerate() {
+            assert_eq!(&recording.state_at(idx), snapshot);
+        }
+
+        let mut state = recording.state_at(0);
+        recording.advance(&mut state, 1, 4);
+        assert_eq!(state, snapshots[4]);
+    }
+
+    #[test]
+    fn test_record_moves() {
+        let first = Whazzup::parse(FIXTURE).unwrap();
+        let mut second = first.clone();
+        // Only the connection time of DLH4AB changes, and BAW123 just moves.
+        second.clients.pilots[1].time += 15;
+        let track = second.clients.pilots[0].last_track.as_mut().unwrap();
+        track.longitude -= 0.5;
+        track.project();
+
+        let mut contents = Vec::new();
+        let mut recorder = Recorder::new(&mut contents, DEFAULT_KEYFRAME_INTERVAL);
+        recorder.record(from_millis(0), &first).unwrap();
+        recorder.record(from_millis(1000), &second).unwrap();
+        let lengths = contents.split(|&b| b == b'\n')
+            .map(<[u8]>::len)
+            .collect::<Vec<_>>();
+        let (snapshot_len, diff_len) = (lengths[0], lengths[1]);
+
+        let recording = Recording::parse(contents.as_slice()).unwrap();
+        let events = match &recording.records[1] {
+            Record::Diff { events, .. } => events,
+            record => panic!("expected a diff, got {:?}", record),
+        };
+        assert_eq!(events.len(), 2);
+        assert!(events.iter().all(|e| matches!(e, TrafficEvent::PilotMoved { .. })));
+        assert!(diff_len * 2 < snapshot_len, "diff is {} bytes, snapshot is {}", diff_len, snapshot_len);
+        assert_eq!(recording.state_at(1), second);
+    }
+
+    #[test]
+    fn test_index_at() {
+        let recording = record(&timeline(), DEFAULT_KEYFRAME_INTERVAL);
+        assert_eq!(recording.index_at(0), 0);
+        assert_eq!(recording.index_at(999), 0);
+        assert_eq!(recording.index_at(1000), 1);
+        assert_eq!(recording.index_at(60_000), 4);
+    }
+
+    #[test]
+    fn test_parse_truncated() {
+        let snapshots = timeline();
+        let mut contents = Vec::new();
+        let mut recorder = Recorder::new(&mut contents, DEFAULT_KEYFRAME_INTERVAL);
+        recorder.record(from_millis(0), &snapshots[0]).unwrap();
+        recorder.record(from_millis(1000), &snapshots[1]).unwrap();
+
+        let truncated = &contents[..contents.len() - 10];
+        assert_eq!(Recording::parse(truncated).unwrap().records.len(), 1);
+
+        contents.extend_from_slice(b"{}\n");
+        contents.extend_from_slice(b"{}\n");
+        assert!(Recording::parse(contents.as_slice()).is_err());
+        assert!(Recording::parse(&b""[..]).is_err());
+    }
+
+    #[test]
+    fn test_append_after_truncation() {
+        let snapshots = timeline();
+        let path = std::env::temp_dir().join(format!("open-aird-recording-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut recorder = Recorder::create(&path).unwrap();
+        recorder.record(from_millis(0), &snapshots[0]).unwrap();
+        recorder.record(from_millis(1000), &snapshots[1]).unwrap();
+        drop(recorder);
+
+        // Interrupt the recorder part way through its last record.
+        let contents = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &contents[..contents.len() - 10]).unwrap();
+
+        let mut recorder = Recorder::create(&path).unwrap();
+        recorder.record(from_millis(2000), &snapshots[2]).unwrap();
+        recorder.record(from_millis(3000), &snapshots[3]).unwrap();
+        drop(recorder);
+
+        let recording = Recording::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        let recording = recording.unwrap();
+        let times = recording.records.iter().map(Record::time).collect::<Vec<_>>();
+        assert_eq!(times, vec![0, 2000, 3000]);
+        assert_eq!(recording.state_at(2), snapshots[3]);
+    }
+
+    #[test]
+    fn test_parse_segments() {
+        let snapshots = timeline();
+        let mut contents = Vec::new();
+        let mut recorder = Recorder::new(&mut contents, DEFAULT_KEYFRAME_INTERVAL);
+        for (idx, snapshot) in snapshots[..3].iter().enumerate() {
+            recorder.record(from_millis(1000 * idx as u64), snapshot).unwrap();
+        }
+
+        // A later session whose clock is behind the first.
+        let mut recorder = Recorder::new(&mut contents, DEFAULT_KEYFRAME_INTERVAL);
+        recorder.record(from_millis(1500), &snapshots[3]).unwrap();
+        recorder.record(from_millis(2500), &snapshots[4]).unwrap();
+
+        let recording = Recording::parse(contents.as_slice()).unwrap();
+        let times = recording.records.iter().map(Record::time).collect::<Vec<_>>();
+        assert_eq!(times, vec![0, 1000, 1500, 2500]);
+        assert_eq!(recording.state_at(1), snapshots[1]);
+        assert_eq!(recording.state_at(3), snapshots[4]);
+
+        // Changes can't go back in time on their own.
+        let mut contents = Vec::new();
+        let mut recorder = Recorder::new(&mut contents, DEFAULT_KEYFRAME_INTERVAL);
+        recorder.record(from_millis(1000), &snapshots[0]).unwrap();
+        recorder.record(from_millis(500), &snapshots[1]).unwrap();
+        assert!(Recording::parse(contents.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_control() {
+        let replay = Replay::new(record(&timeline(), 2), 1.).unwrap();
+        replay.control(&ReplayControl { seek: Some(2500), paused: Some(true), speed: Some(4.) }).unwrap();
+        let status = replay.status();
+        assert_eq!(status.position, 2500);
+        assert_eq!(status.speed, 4.);
+        assert!(status.paused);
+
+        replay.control(&ReplayControl { seek: Some(u64::MAX), ..Default::default() }).unwrap();
+        assert_eq!(replay.status().position, 4000);
+        assert!(replay.control(&ReplayControl { speed: Some(0.), ..Default::default() }).is_err());
+        assert!(Replay::new(record(&timeline(), 2), -1.).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_replay() {
+        let snapshots = timeline();
+        let replay = Replay::new(record(&snapshots, 2), 1.).unwrap();
+        replay.control(&ReplayControl { seek: Some(3000), paused: Some(true), ..Default::default() }).unwrap();
+
+        let cache = WhazzupCache::default();
+        let mut receiver = cache.subscribe();
+        tokio::spawn(replay.clone().run(cache.clone()));
+
+        receiver.changed().await.unwrap();
+        assert_eq!(cache.latest().unwrap().whazzup, snapshots[3]);
+        assert_eq!(cache.latest().unwrap().fetched_at, from_millis(3000));
+
+        replay.control(&ReplayControl { seek: Some(1000), ..Default::default() }).unwrap();
+        receiver.changed().await.unwrap();
+        assert_eq!(cache.latest().unwrap().whazzup, snapshots[1]);
+    }
+}
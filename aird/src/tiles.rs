@@ -0,0 +1,145 @@
+//! Serving the `global.json` and section tiles produced by `convert_sectors`.
+
+use std::io::ErrorKind;
+use std::path::{Path as FsPath, PathBuf};
+use std::sync::Arc;
+
+use axum::extract::{Path, State};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use log::warn;
+
+use open_air::domain::coords::Projection;
+use open_air::domain::viewer::Global;
+
+use crate::caching::Validators;
+
+const CACHE_CONTROL: &str = "public, max-age=60";
+
+/// Check whether a requested file name is one of the generated tiles.
+///
+/// This is deliberately strict so that nothing else in the data directory
+/// can be requested.
+pub fn is_tile_name(name: &str) -> bool {
+    if name == "global.json" {
+        return true;
+    }
+
+    let division = match name.strip_prefix("section_").and_then(|n| n.strip_suffix(".json")) {
+        Some(x) => x,
+        None => return false,
+    };
+
+    let parts = division.split('_').collect::<Vec<_>>();
+    parts.len() == 3 && parts.iter().all(|p| {
+        let digits = p.strip_prefix('-').unwrap_or(p);
+        !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())
+    })
+}
+
+/// Read the projection the tiles in a data directory were made with.
+///
+/// A directory without a `global.json` has no tiles, so Mercator is used.
+pub fn load_projection(data_dir: &FsPath) -> anyhow::Result<Projection> {
+    let contents = match std::fs::read(data_dir.join("global.json")) {
+        Ok(x) => x,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Projection::Mercator),
+        Err(err) => return Err(err.into()),
+    };
+    let global: Global = serde_json::from_slice(&contents)?;
+    Ok(global.projection)
+}
+
+pub struct TileStore {
+    data_dir: PathBuf,
+}
+
+impl TileStore {
+    pub fn new(data_dir: PathBuf) -> TileStore {
+        TileStore {
+            data_dir,
+        }
+    }
+}
+
+pub async fn get_tile(State(store): State<Arc<TileStore>>, Path(name): Path<String>, headers: HeaderMap) -> Response {
+    if !is_tile_name(&name) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    let path = store.data_dir.join(&name);
+    let metadata = match tokio::fs::metadata(&path).await {
+        Ok(x) => x,
+        Err(err) if err.kind() == ErrorKind::NotFound => return StatusCode::NOT_FOUND.into_response(),
+        Err(err) => {
+            warn!("failed to stat {:?}: {}", path, err);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let validators = match metadata.modified() {
+        Ok(modified) => Validators::new(metadata.len(), modified),
+        Err(err) => {
+            warn!("failed to get modification time of {:?}: {}", path, err);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let mut response = if validators.is_not_modified(&headers) {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        match tokio::fs::read(&path).await {
+            Ok(contents) => {
+                let mut response = contents.into_response();
+                response.headers_mut().insert(
+                    header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
+                response
+            }
+            Err(err) => {
+                warn!("failed to read {:?}: {}", path, err);
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        }
+    };
+
+    let headers = response.headers_mut();
+    validators.apply(headers);
+    headers.insert(header::CACHE_CONTROL, HeaderValue::from_static(CACHE_CONTROL));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use open_air::domain::coords::GeoPosition;
+
+    use super::*;
+
+    #[test]
+    fn test_tile_names() {
+        assert!(is_tile_name("global.json"));
+        assert!(is_tile_name("section_003_004_005.json"));
+        assert!(!is_tile_name("section_003_004.json"));
+        assert!(!is_tile_name("section_003_004_../a.json"));
+        assert!(!is_tile_name("../global.json"));
+        assert!(!is_tile_name("Sector.isc"));
+    }
+
+    #[test]
+    fn test_load_projection() {
+        let dir = std::env::temp_dir().join(format!("open-aird-tiles-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        assert_eq!(load_projection(&dir).unwrap(), Projection::Mercator);
+
+        let projection = Projection::Stereographic {
+            center: GeoPosition::new(60., 24.),
+        };
+        let global = Global {
+            projection,
+            ..Default::default()
+        };
+        std::fs::write(dir.join("global.json"), serde_json::to_vec(&global).unwrap()).unwrap();
+        let loaded = load_projection(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(loaded.unwrap(), projection);
+    }
+}
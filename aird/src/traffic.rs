@@ -0,0 +1,454 @@
+//! Incremental traffic updates, computed by diffing successive whazzup snapshots.
+//!
+//! Viewers subscribe to `/traffic` and receive a stream of server-sent events,
+//! each containing the list of changes since the previous update. The first
+//! update contains everything currently connected.
+
+use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use serde::{Deserialize, Serialize};
+use tokio_stream::{Stream, StreamExt};
+use tokio_stream::wrappers::WatchStream;
+
+use ivao::whazzup::{Atc, Pilot, Track, Whazzup};
+use open_air::domain::viewer::normalise_aabb;
+
+use crate::whazzup::{Snapshot, WhazzupCache};
+
+/// A single change to the traffic picture.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum TrafficEvent {
+    PilotAdded { pilot: Pilot },
+    /// Something other than the position of a pilot changed, such as the flight
+    /// plan.
+    PilotUpdated { pilot: Pilot },
+    /// Only the position and connection time of a pilot changed. `track` is
+    /// `None` if the pilot has no position.
+    PilotMoved { callsign: String, time: u64, track: Option<Track> },
+    PilotRemoved { callsign: String },
+    AtcOnline { atc: Atc },
+    /// The session of a controller changed, such as their frequency or ATIS.
+    AtcUpdated { atc: Atc },
+    AtcOffline { callsign: String },
+}
+
+impl TrafficEvent {
+    pub fn callsign(&self) -> &str {
+        match self {
+            TrafficEvent::PilotAdded { pilot } | TrafficEvent::PilotUpdated { pilot } => &pilot.callsign,
+            TrafficEvent::AtcOnline { atc } | TrafficEvent::AtcUpdated { atc } => &atc.callsign,
+            TrafficEvent::PilotMoved { callsign, .. }
+            | TrafficEvent::PilotRemoved { callsign }
+            | TrafficEvent::AtcOffline { callsign } => callsign,
+        }
+    }
+}
+
+/// Calculate the changes required to get from one snapshot to another.
+pub fn diff(old: &Whazzup, new: &Whazzup) -> Vec<TrafficEvent> {
+    let mut events = Vec::new();
+
+    let old_pilots = old.clients.pilots.iter()
+        .map(|p| (p.callsign.as_str(), p))
+        .collect::<HashMap<_, _>>();
+    let new_pilots = new.clients.pilots.iter()
+        .map(|p| p.callsign.as_str())
+        .collect::<HashSet<_>>();
+
+    for pilot in new.clients.pilots.iter() {
+        let old = match old_pilots.get(pilot.callsign.as_str()) {
+            Some(x) => x,
+            None => {
+                events.push(TrafficEvent::PilotAdded { pilot: pilot.clone() });
+                continue;
+            }
+        };
+
+        // The connection time and track change in every snapshot, anything
+        // else needs the whole pilot.
+        let masked = Pilot {
+            time: pilot.time,
+            last_track: pilot.last_track.clone(),
+            ..(*old).clone()
+        };
+        if masked != *pilot {
+            events.push(TrafficEvent::PilotUpdated { pilot: pilot.clone() });
+        } else if old.time != pilot.time || old.last_track != pilot.last_track {
+            events.push(TrafficEvent::PilotMoved {
+                callsign: pilot.callsign.clone(),
+                time: pilot.time,
+                track: pilot.last_track.clone(),
+            });
+        }
+    }
+
+    for pilot in old.clients.pilots.iter() {
+        if !new_pilots.contains(pilot.callsign.as_str()) {
+            events.push(TrafficEvent::PilotRemoved { callsign: pilot.callsign.clone() });
+        }
+    }
+
+    let old_atcs = old.clients.atcs.iter()
+        .map(|a| (a.callsign.as_str(), a))
+        .collect::<HashMap<_, _>>();
+    let new_atcs = new.clients.atcs.iter()
+        .map(|a| a.callsign.as_str())
+        .collect::<HashSet<_>>();
+
+    for atc in new.clients.atcs.iter() {
+        let old = match old_atcs.get(atc.callsign.as_str()) {
+            Some(x) => x,
+            None => {
+                events.push(TrafficEvent::AtcOnline { atc: atc.clone() });
+                continue;
+            }
+        };
+
+        // Controllers don't move, so only their session and position are
+        // compared, rather than the connection time and track details which
+        // change in every snapshot.
+        let masked = Atc {
+            time: atc.time,
+            last_track: atc.last_track.clone(),
+            ..(*old).clone()
+        };
+        let position = |a: &Atc| a.last_track.as_ref().map(Track::map_position);
+        if masked != *atc || position(old) != position(atc) {
+            events.push(TrafficEvent::AtcUpdated { atc: atc.clone() });
+        }
+    }
+
+    for atc in old.clients.atcs.iter() {
+        if !new_atcs.contains(atc.callsign.as_str()) {
+            events.push(TrafficEvent::AtcOffline { callsign: atc.callsign.clone() });
+        }
+    }
+
+    events
+}
+
+/// Apply the changes calculated by [`diff`] to a snapshot.
+///
+/// Clients which are added are placed at the end of the list, so the order may
+/// differ from the snapshot the changes were calculated against. The connection
+/// time and track details of controllers are only brought up to date when their
+/// session changes.
+pub fn apply(whazzup: &mut Whazzup, events: &[TrafficEvent]) {
+    let pilots = &mut whazzup.clients.pilots;
+    let atcs = &mut whazzup.clients.atcs;
+
+    for event in events {
+        match event {
+            TrafficEvent::PilotAdded { pilot } | TrafficEvent::PilotUpdated { pilot } => {
+                match pilots.iter_mut().find(|p| p.callsign == pilot.callsign) {
+                    Some(existing) => *existing = pilot.clone(),
+                    None => pilots.push(pilot.clone()),
+                }
+            }
+            TrafficEvent::PilotMoved { callsign, time, track } => {
+                if let Some(pilot) = pilots.iter_mut().find(|p| &p.callsign == callsign) {
+                    pilot.time = *time;
+                    pilot.last_track = track.clone();
+                }
+            }
+            TrafficEvent::PilotRemoved { callsign } => pilots.retain(|p| &p.callsign != callsign),
+            TrafficEvent::AtcOnline { atc } | TrafficEvent::AtcUpdated { atc } => {
+                match atcs.iter_mut().find(|a| a.callsign == atc.callsign) {
+                    Some(existing) => *existing = atc.clone(),
+                    None => atcs.push(atc.clone()),
+                }
+            }
+            TrafficEvent::AtcOffline { callsign } => atcs.retain(|a| &a.callsign != callsign),
+        }
+    }
+}
+
+/// Restricts the traffic events sent to a viewer to those within its map bounds.
+///
+/// The filter remembers which clients the viewer has been told about, so that
+/// traffic leaving the bounds is sent as a removal and traffic entering them is
+/// sent as an addition.
+#[derive(Debug, Default)]
+pub struct TrafficFilter {
+    bounds: Option<(f64, f64, f64, f64)>,
+    visible: HashSet<String>,
+}
+
+impl TrafficFilter {
+    pub fn new(bounds: Option<(f64, f64, f64, f64)>) -> TrafficFilter {
+        TrafficFilter {
+            bounds: bounds.map(normalise_aabb),
+            visible: HashSet::new(),
+        }
+    }
+
+    fn contains(&self, track: Option<&Track>) -> bool {
+        match (self.bounds, track) {
+            (None, _) => true,
+            (Some(_), None) => false,
+            (Some((min_x, min_y, max_x, max_y)), Some(track)) => {
+                let (x, y) = track.map_position();
+                x >= min_x && x <= max_x && y >= min_y && y <= max_y
+            }
+        }
+    }
+
+    fn filter_one(&mut self, event: TrafficEvent, snapshot: &Whazzup) -> Option<TrafficEvent> {
+        let callsign = event.callsign().to_owned();
+        let was_visible = self.visible.contains(&callsign);
+
+        let is_visible = match &event {
+            TrafficEvent::PilotAdded { pilot } | TrafficEvent::PilotUpdated { pilot } =>
+                self.contains(pilot.last_track.as_ref()),
+            TrafficEvent::PilotMoved { track, .. } => self.contains(track.as_ref()),
+            TrafficEvent::AtcOnline { atc } | TrafficEvent::AtcUpdated { atc } =>
+                self.contains(atc.last_track.as_ref()),
+            TrafficEvent::PilotRemoved { .. } | TrafficEvent::AtcOffline { .. } => false,
+        };
+
+        if is_visible {
+            self.visible.insert(callsign.clone());
+        } else {
+            self.visible.remove(&callsign);
+        }
+
+        match (was_visible, is_visible, event) {
+            (false, false, _) => None,
+            (true, false, TrafficEvent::AtcOnline { .. } | TrafficEvent::AtcUpdated { .. }) =>
+                Some(TrafficEvent::AtcOffline { callsign }),
+            (true, false, TrafficEvent::PilotAdded { .. } | TrafficEvent::PilotUpdated { .. }
+                | TrafficEvent::PilotMoved { .. }) =>
+                Some(TrafficEvent::PilotRemoved { callsign }),
+            (false, true, TrafficEvent::PilotUpdated { pilot }) =>
+                Some(TrafficEvent::PilotAdded { pilot }),
+            (false, true, TrafficEvent::PilotMoved { .. }) => snapshot.pilot(&callsign)
+                .map(|pilot| TrafficEvent::PilotAdded { pilot: pilot.clone() }),
+            (false, true, TrafficEvent::AtcUpdated { atc }) =>
+                Some(TrafficEvent::AtcOnline { atc }),
+            (_, _, event) => Some(event),
+        }
+    }
+
+    /// Filter a set of events, given the snapshot they lead to.
+    pub fn filter(&mut self, events: Vec<TrafficEvent>, snapshot: &Whazzup) -> Vec<TrafficEvent> {
+        events.into_iter()
+            .filter_map(|event| self.filter_one(event, snapshot))
+            .collect()
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct TrafficQuery {
+    /// The visible map bounds as `minX,minY,maxX,maxY`, in the map coordinates
+    /// of the tiles being served.
+    bounds: Option<String>,
+}
+
+fn parse_bounds(src: &str) -> Option<(f64, f64, f64, f64)> {
+    let parts = src.split(',')
+        .map(|p| p.trim().parse::<f64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    match parts.as_slice() {
+        [a, b, c, d] => Some((*a, *b, *c, *d)),
+        _ => None,
+    }
+}
+
+/// The changes a viewer sees as the cache is updated, restricted to its bounds.
+///
+/// Updates which don't change anything within the bounds are skipped.
+pub fn traffic_events(cache: &WhazzupCache, bounds: Option<(f64, f64, f64, f64)>)
+                      -> impl Stream<Item=Vec<TrafficEvent>> {
+    let empty = Whazzup::default();
+    let mut filter = TrafficFilter::new(bounds);
+    let mut previous: Option<Arc<Snapshot>> = None;
+    WatchStream::new(cache.subscribe())
+        .filter_map(move |snapshot| {
+            let snapshot = snapshot?;
+            let old = previous.as_ref().map_or(&empty, |s| &s.whazzup);
+            let events = diff(old, &snapshot.whazzup);
+            let events = filter.filter(events, &snapshot.whazzup);
+            previous = Some(snapshot);
+
+            Some(events).filter(|events| !events.is_empty())
+        })
+}
+
+pub async fn get_traffic(State(cache): State<WhazzupCache>, Query(query): Query<TrafficQuery>) -> Response {
+    let bounds = match query.bounds.as_deref().map(parse_bounds) {
+        Some(None) => return (StatusCode::BAD_REQUEST, "invalid bounds").into_response(),
+        Some(Some(bounds)) => Some(bounds),
+        None => None,
+    };
+
+    let stream = traffic_events(&cache, bounds)
+        .filter_map(|events| {
+            Event::default()
+                .event("traffic")
+                .json_data(&events)
+                .ok()
+        })
+        .map(Ok::<_, Infallible>);
+
+    Sse::new(stream)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use super::*;
+
+    const FIXTURE: &[u8] = include_bytes!("../../ivao/fixtures/whazzup_v2.json");
+
+    fn fixture() -> Whazzup {
+        Whazzup::parse(FIXTURE).unwrap()
+    }
+
+    fn move_pilot(whazzup: &mut Whazzup, callsign: &str, latitude: f64, longitude: f64) {
+        let pilot = whazzup.clients.pilots.iter_mut()
+            .find(|p| p.callsign == callsign)
+            .unwrap();
+        let track = pilot.last_track.get_or_insert_with(Default::default);
+        track.latitude = latitude;
+        track.longitude = longitude;
+        track.project();
+    }
+
+    #[test]
+    fn test_diff() {
+        let first = fixture();
+        let events = diff(&Whazzup::default(), &first);
+        assert_eq!(events.len(), 3);
+        assert!(matches!(&events[0], TrafficEvent::PilotAdded { pilot } if pilot.callsign == "BAW123"));
+        assert!(matches!(&events[2], TrafficEvent::AtcOnline { atc } if atc.callsign == "EGLL_TWR"));
+
+        assert!(diff(&first, &first).is_empty());
+
+        let mut second = first.clone();
+        move_pilot(&mut second, "BAW123", 52.5, -9.);
+        second.clients.pilots.retain(|p| p.callsign != "DLH4AB");
+        second.clients.atcs.clear();
+
+        let events = diff(&first, &second);
+        assert_eq!(events.len(), 3);
+        assert!(matches!(&events[0], TrafficEvent::PilotMoved { callsign, .. } if callsign == "BAW123"));
+        assert_eq!(events[1], TrafficEvent::PilotRemoved { callsign: "DLH4AB".into() });
+        assert_eq!(events[2], TrafficEvent::AtcOffline { callsign: "EGLL_TWR".into() });
+
+        let mut third = second.clone();
+        third.clients.pilots[0].time += 60;
+        third.clients.pilots[0].last_track.as_mut().unwrap().time += 60;
+        let events = diff(&second, &third);
+        assert_eq!(events, vec![TrafficEvent::PilotMoved {
+            callsign: "BAW123".into(),
+            time: third.clients.pilots[0].time,
+            track: third.clients.pilots[0].last_track.clone(),
+        }]);
+
+        let mut fourth = third.clone();
+        fourth.clients.pilots[0].last_track = None;
+        let events = diff(&third, &fourth);
+        assert_eq!(events, vec![TrafficEvent::PilotMoved {
+            callsign: "BAW123".into(),
+            time: third.clients.pilots[0].time,
+            track: None,
+        }]);
+
+        let mut fifth = fourth.clone();
+        fifth.clients.pilots[0].flight_plan = None;
+        let events = diff(&fourth, &fifth);
+        assert_eq!(events, vec![TrafficEvent::PilotUpdated { pilot: fifth.clients.pilots[0].clone() }]);
+    }
+
+    #[test]
+    fn test_diff_atc() {
+        let first = fixture();
+
+        let mut second = first.clone();
+        second.clients.atcs[0].time += 60;
+        second.clients.atcs[0].last_track.as_mut().unwrap().time += 60;
+        assert!(diff(&first, &second).is_empty());
+
+        let mut third = second.clone();
+        third.clients.atcs[0].atc_session.as_mut().unwrap().frequency = 121.9;
+        let events = diff(&second, &third);
+        assert_eq!(events, vec![TrafficEvent::AtcUpdated { atc: third.clients.atcs[0].clone() }]);
+    }
+
+    #[test]
+    fn test_apply() {
+        let first = fixture();
+        let mut state = Whazzup::default();
+        apply(&mut state, &diff(&Whazzup::default(), &first));
+        assert_eq!(state.clients.pilots, first.clients.pilots);
+        assert_eq!(state.clients.atcs, first.clients.atcs);
+
+        let mut second = first.clone();
+        move_pilot(&mut second, "BAW123", 52.5, -9.);
+        second.clients.pilots.retain(|p| p.callsign != "DLH4AB");
+        second.clients.atcs.clear();
+        apply(&mut state, &diff(&first, &second));
+        assert_eq!(state.clients.pilots, second.clients.pilots);
+        assert!(state.clients.atcs.is_empty());
+    }
+
+    #[test]
+    fn test_filter() {
+        let first = fixture();
+
+        // Around Heathrow, but not the west of Ireland.
+        let (min_x, min_y) = open_air::domain::coords::geo_to_map(52., -1.);
+        let (max_x, max_y) = open_air::domain::coords::geo_to_map(51., 0.);
+        let mut filter = TrafficFilter::new(Some((min_x, min_y, max_x, max_y)));
+
+        let events = filter.filter(diff(&Whazzup::default(), &first), &first);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].callsign(), "EGLL_TWR");
+
+        let mut second = first.clone();
+        move_pilot(&mut second, "BAW123", 51.5, -0.5);
+        let events = filter.filter(diff(&first, &second), &second);
+        assert_eq!(events.len(), 1);
+        assert!(matches!(&events[0], TrafficEvent::PilotAdded { pilot } if pilot.callsign == "BAW123"));
+
+        let events = filter.filter(diff(&second, &first), &first);
+        assert_eq!(events, vec![TrafficEvent::PilotRemoved { callsign: "BAW123".into() }]);
+    }
+
+    #[test]
+    fn test_parse_bounds() {
+        assert_eq!(parse_bounds("0.1,0.2,0.3,0.4"), Some((0.1, 0.2, 0.3, 0.4)));
+        assert_eq!(parse_bounds("0.1,0.2,0.3"), None);
+        assert_eq!(parse_bounds("a,b,c,d"), None);
+    }
+
+    #[tokio::test]
+    async fn test_traffic_events() {
+        let cache = WhazzupCache::default();
+        let events = traffic_events(&cache, None);
+        tokio::pin!(events);
+
+        let first = fixture();
+        cache.update(first.clone(), SystemTime::now()).unwrap();
+        assert_eq!(events.next().await.unwrap(), diff(&Whazzup::default(), &first));
+
+        let mut second = first.clone();
+        second.clients.pilots[0].rating += 1;
+        move_pilot(&mut second, "DLH4AB", 50., 8.);
+        cache.update(second.clone(), SystemTime::now()).unwrap();
+        let received = events.next().await.unwrap();
+        assert_eq!(received.len(), 2);
+        assert_eq!(received[0], TrafficEvent::PilotUpdated { pilot: second.clients.pilots[0].clone() });
+        assert!(matches!(&received[1], TrafficEvent::PilotMoved { callsign, .. } if callsign == "DLH4AB"));
+    }
+}
@@ -0,0 +1,225 @@
+//! Polling the whazzup feed and serving the latest snapshot to viewers.
+//!
+//! Only one request is made to the upstream source per interval, no matter how
+//! many viewers are connected. Tracks are projected the same way as the sector
+//! tiles being served, so their map positions line up with the map.
+
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use axum::extract::State;
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use log::{info, warn};
+use tokio::sync::watch;
+
+use ivao::whazzup::Whazzup;
+use open_air::domain::coords::Projection;
+
+use crate::caching::Validators;
+
+/// Where to fetch whazzup snapshots from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WhazzupSource {
+    Url(String),
+    /// A local file, mostly useful for testing.
+    File(PathBuf),
+}
+
+impl WhazzupSource {
+    /// Fetch the raw contents of the feed.
+    pub async fn fetch(&self, client: &reqwest::Client) -> anyhow::Result<Vec<u8>> {
+        match self {
+            WhazzupSource::Url(url) => {
+                let response = client.get(url)
+                    .header(header::ACCEPT, "application/json")
+                    .send()
+                    .await?
+                    .error_for_status()?;
+                Ok(response.bytes().await?.to_vec())
+            }
+            WhazzupSource::File(path) => Ok(tokio::fs::read(path).await?),
+        }
+    }
+}
+
+impl FromStr for WhazzupSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<WhazzupSource> {
+        if s.starts_with("http://") || s.starts_with("https://") {
+            Ok(WhazzupSource::Url(s.to_owned()))
+        } else {
+            Ok(WhazzupSource::File(PathBuf::from(s)))
+        }
+    }
+}
+
+/// A parsed whazzup snapshot, along with its serialized form.
+#[derive(Debug)]
+pub struct Snapshot {
+    pub whazzup: Whazzup,
+    pub fetched_at: SystemTime,
+    pub body: Vec<u8>,
+    pub validators: Validators,
+}
+
+impl Snapshot {
+    pub fn new(whazzup: Whazzup, fetched_at: SystemTime) -> anyhow::Result<Snapshot> {
+        let body = serde_json::to_vec(&whazzup)?;
+        let validators = Validators::for_contents(&body, fetched_at);
+        Ok(Snapshot {
+            whazzup,
+            fetched_at,
+            body,
+            validators,
+        })
+    }
+}
+
+/// The most recent whazzup snapshot.
+#[derive(Clone)]
+pub struct WhazzupCache {
+    latest: Arc<watch::Sender<Option<Arc<Snapshot>>>>,
+    projection: Projection,
+}
+
+impl Default for WhazzupCache {
+    fn default() -> Self {
+        WhazzupCache::with_projection(Projection::Mercator)
+    }
+}
+
+impl WhazzupCache {
+    /// Create a cache which projects every track onto the map with `projection`.
+    pub fn with_projection(projection: Projection) -> WhazzupCache {
+        WhazzupCache {
+            latest: Arc::new(watch::channel(None).0),
+            projection,
+        }
+    }
+
+    /// Get the most recent snapshot, if one has been fetched.
+    pub fn latest(&self) -> Option<Arc<Snapshot>> {
+        self.latest.borrow().clone()
+    }
+
+    /// Subscribe to be notified when the latest snapshot changes.
+    pub fn subscribe(&self) -> watch::Receiver<Option<Arc<Snapshot>>> {
+        self.latest.subscribe()
+    }
+
+    /// Replace the latest snapshot, returning whether anything changed.
+    ///
+    /// Every track is projected again with the cache's projection. If the
+    /// contents are then identical to the current snapshot, the existing
+    /// snapshot is kept so that its validators stay the same.
+    pub fn update(&self, mut whazzup: Whazzup, fetched_at: SystemTime) -> anyhow::Result<bool> {
+        whazzup.project_with(&self.projection);
+        if self.latest().is_some_and(|s| s.whazzup == whazzup) {
+            return Ok(false);
+        }
+
+        let snapshot = Snapshot::new(whazzup, fetched_at)?;
+        self.latest.send_replace(Some(Arc::new(snapshot)));
+        Ok(true)
+    }
+}
+
+/// Poll the source forever, updating the cache with each new snapshot.
+pub async fn poll(source: WhazzupSource, interval: Duration, cache: WhazzupCache) {
+    let client = reqwest::Client::new();
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        ticker.tick().await;
+
+        let result = async {
+            let contents = source.fetch(&client).await?;
+            let whazzup = Whazzup::parse(&contents)?;
+            cache.update(whazzup, SystemTime::now())
+        }.await;
+
+        match result {
+            Ok(true) => info!("fetched new whazzup snapshot"),
+            Ok(false) => {}
+            Err(err) => warn!("failed to fetch whazzup from {:?}: {}", source, err),
+        }
+    }
+}
+
+pub async fn get_whazzup(State(cache): State<WhazzupCache>, headers: HeaderMap) -> Response {
+    let snapshot = match cache.latest() {
+        Some(x) => x,
+        None => return StatusCode::SERVICE_UNAVAILABLE.into_response(),
+    };
+
+    let mut response = if snapshot.validators.is_not_modified(&headers) {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        let mut response = snapshot.body.clone().into_response();
+        response.headers_mut().insert(
+            header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        response
+    };
+
+    let headers = response.headers_mut();
+    snapshot.validators.apply(headers);
+    headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use open_air::domain::coords::GeoPosition;
+
+    use super::*;
+
+    const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../ivao/fixtures/whazzup_v2.json");
+
+    #[test]
+    fn test_parse_source() {
+        assert_eq!("https://api.ivao.aero/v2/tracker/whazzup".parse::<WhazzupSource>().unwrap(),
+                   WhazzupSource::Url("https://api.ivao.aero/v2/tracker/whazzup".into()));
+        assert_eq!("whazzup.json".parse::<WhazzupSource>().unwrap(),
+                   WhazzupSource::File("whazzup.json".into()));
+    }
+
+    #[tokio::test]
+    async fn test_update() {
+        let source = WhazzupSource::File(FIXTURE.into());
+        let contents = source.fetch(&reqwest::Client::new()).await.unwrap();
+        let whazzup = Whazzup::parse(&contents).unwrap();
+
+        let cache = WhazzupCache::default();
+        assert!(cache.latest().is_none());
+        assert!(cache.update(whazzup.clone(), SystemTime::now()).unwrap());
+        let first = cache.latest().unwrap();
+
+        assert!(!cache.update(whazzup.clone(), SystemTime::now()).unwrap());
+        assert!(Arc::ptr_eq(&first, &cache.latest().unwrap()));
+
+        let mut changed = whazzup;
+        changed.clients.pilots.pop();
+        assert!(cache.update(changed, SystemTime::now()).unwrap());
+        assert_ne!(first.validators.etag, cache.latest().unwrap().validators.etag);
+    }
+
+    #[test]
+    fn test_update_projection() {
+        let whazzup = Whazzup::parse(&std::fs::read(FIXTURE).unwrap()).unwrap();
+        let projection = Projection::Stereographic {
+            center: GeoPosition::new(60., 24.),
+        };
+        let cache = WhazzupCache::with_projection(projection);
+        cache.update(whazzup, SystemTime::now()).unwrap();
+
+        let latest = cache.latest().unwrap();
+        let track = latest.whazzup.pilot("BAW123").unwrap().last_track.as_ref().unwrap();
+        let expected = projection.project(GeoPosition::new(track.latitude, track.longitude));
+        assert_eq!(track.map_position(), expected);
+    }
+}
@@ -1,4 +1,15 @@
 use std::f64::consts::PI;
+use std::fmt::{self, Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+use crate::domain::parsing::ParseError;
+
+/// The mean radius of the Earth, in nautical miles.
+///
+/// The great-circle functions below treat the Earth as a sphere of this radius,
+/// which is within about 0.5% of WGS84 everywhere.
+pub const EARTH_RADIUS_NM: f64 = 3440.065;
 
 pub fn geo_to_map(latitude: f64, longitude: f64) -> (f64, f64) {
     let x = (longitude + 180.) / 360.;
@@ -14,6 +25,83 @@ pub fn map_to_geo(x: f64, y: f64) -> (f64, f64) {
     (latitude, longitude)
 }
 
+/// The great-circle distance between two `(latitude, longitude)` positions, in nautical miles.
+pub fn distance_nm(a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (lat_a, lon_a) = (a.0.to_radians(), a.1.to_radians());
+    let (lat_b, lon_b) = (b.0.to_radians(), b.1.to_radians());
+    let h = ((lat_b - lat_a) / 2.).sin().powi(2)
+        + lat_a.cos() * lat_b.cos() * ((lon_b - lon_a) / 2.).sin().powi(2);
+    2. * EARTH_RADIUS_NM * h.sqrt().min(1.).asin()
+}
+
+/// The initial great-circle bearing from one `(latitude, longitude)` position to another,
+/// in degrees clockwise from true north.
+pub fn initial_bearing(a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (lat_a, lon_a) = (a.0.to_radians(), a.1.to_radians());
+    let (lat_b, lon_b) = (b.0.to_radians(), b.1.to_radians());
+    let y = (lon_b - lon_a).sin() * lat_b.cos();
+    let x = lat_a.cos() * lat_b.sin() - lat_a.sin() * lat_b.cos() * (lon_b - lon_a).cos();
+    y.atan2(x).to_degrees().rem_euclid(360.)
+}
+
+/// The bearing a great circle from one `(latitude, longitude)` position to another
+/// arrives at the second on, in degrees clockwise from true north.
+pub fn final_bearing(a: (f64, f64), b: (f64, f64)) -> f64 {
+    (initial_bearing(b, a) + 180.).rem_euclid(360.)
+}
+
+/// The `(latitude, longitude)` position reached by travelling `distance` nautical
+/// miles from `start` along a great circle with the given initial bearing.
+pub fn destination(start: (f64, f64), bearing: f64, distance: f64) -> (f64, f64) {
+    let (lat, lon) = (start.0.to_radians(), start.1.to_radians());
+    let bearing = bearing.to_radians();
+    let angle = distance / EARTH_RADIUS_NM;
+
+    let lat_b = (lat.sin() * angle.cos() + lat.cos() * angle.sin() * bearing.cos()).asin();
+    let lon_b = lon + (bearing.sin() * angle.sin() * lat.cos())
+        .atan2(angle.cos() - lat.sin() * lat_b.sin());
+    (lat_b.to_degrees(), (lon_b.to_degrees() + 540.).rem_euclid(360.) - 180.)
+}
+
+/// How far `point` is from the great circle through `start` and `end`, in nautical miles.
+///
+/// The distance is positive if the point is to the right of the path when
+/// travelling from `start` to `end`, and negative if it is to the left.
+pub fn cross_track_distance_nm(point: (f64, f64), start: (f64, f64), end: (f64, f64)) -> f64 {
+    let angle = distance_nm(start, point) / EARTH_RADIUS_NM;
+    let bearing_point = initial_bearing(start, point).to_radians();
+    let bearing_end = initial_bearing(start, end).to_radians();
+    (angle.sin() * (bearing_point - bearing_end).sin()).asin() * EARTH_RADIUS_NM
+}
+
+/// The `(latitude, longitude)` position `fraction` of the way along the great
+/// circle from `a` to `b`.
+pub fn intermediate_point(a: (f64, f64), b: (f64, f64), fraction: f64) -> (f64, f64) {
+    let angle = distance_nm(a, b) / EARTH_RADIUS_NM;
+    if angle.sin().abs() < 1e-12 {
+        return a;
+    }
+
+    let (lat_a, lon_a) = (a.0.to_radians(), a.1.to_radians());
+    let (lat_b, lon_b) = (b.0.to_radians(), b.1.to_radians());
+    let weight_a = ((1. - fraction) * angle).sin() / angle.sin();
+    let weight_b = (fraction * angle).sin() / angle.sin();
+
+    let x = weight_a * lat_a.cos() * lon_a.cos() + weight_b * lat_b.cos() * lon_b.cos();
+    let y = weight_a * lat_a.cos() * lon_a.sin() + weight_b * lat_b.cos() * lon_b.sin();
+    let z = weight_a * lat_a.sin() + weight_b * lat_b.sin();
+    (z.atan2(x.hypot(y)).to_degrees(), y.atan2(x).to_degrees())
+}
+
+/// Split the great circle from `a` to `b` into `segments` equal parts, returning
+/// the `segments + 1` positions at their ends, for drawing routes.
+pub fn great_circle_points(a: (f64, f64), b: (f64, f64), segments: usize) -> Vec<(f64, f64)> {
+    let segments = segments.max(1);
+    (0..=segments)
+        .map(|i| intermediate_point(a, b, i as f64 / segments as f64))
+        .collect()
+}
+
 pub fn calculate_aabb(mut pts: impl Iterator<Item=(f64, f64)>) -> (f64, f64, f64, f64) {
     let mut aabb = if let Some((x, y)) = pts.next() {
         (x, y, x, y)
@@ -30,3 +118,453 @@ pub fn calculate_aabb(mut pts: impl Iterator<Item=(f64, f64)>) -> (f64, f64, f64
 
     aabb
 }
+
+/// How positions on the Earth are turned into map coordinates.
+///
+/// Every projection produces map coordinates on the same scale as Web
+/// Mercator, where 1 is the circumference of the Earth, and puts its centre
+/// at `(0.5, 0.5)`, so sections can be divided up the same way whichever is
+/// used. The ones other than Mercator are only meaningful for positions
+/// within a hemisphere or so of their centre.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum Projection {
+    /// Web Mercator, see [`geo_to_map`].
+    #[default]
+    Mercator,
+    /// An azimuthal stereographic projection centred on a position, usually
+    /// the centre of the sector, which keeps range rings circular.
+    Stereographic {
+        center: GeoPosition,
+    },
+    /// A Lambert conformal conic projection, true to scale along the two
+    /// standard parallels. Parallels symmetric about the equator make this
+    /// the same as Mercator.
+    LambertConformalConic {
+        origin: GeoPosition,
+        #[serde(rename = "standardParallels")]
+        standard_parallels: (f64, f64),
+    },
+}
+
+/// Scale a position on a unit sphere's projection to map coordinates.
+fn plane_to_map(x: f64, y: f64) -> (f64, f64) {
+    (0.5 + x / (2. * PI), 0.5 - y / (2. * PI))
+}
+
+fn map_to_plane(x: f64, y: f64) -> (f64, f64) {
+    ((x - 0.5) * 2. * PI, (0.5 - y) * 2. * PI)
+}
+
+/// Wrap a longitude, or a difference between two, into [-180, 180).
+fn normalize_longitude(degrees: f64) -> f64 {
+    (degrees + 540.).rem_euclid(360.) - 180.
+}
+
+/// The cone constant and scale of a Lambert conformal conic projection.
+fn lambert_constants(standard_parallels: (f64, f64)) -> (f64, f64) {
+    let (lat_1, lat_2) = (standard_parallels.0.to_radians(), standard_parallels.1.to_radians());
+    let t = |lat: f64| (PI / 4. + lat / 2.).tan();
+    let n = if (lat_1 - lat_2).abs() < 1e-10 {
+        lat_1.sin()
+    } else {
+        (lat_1.cos() / lat_2.cos()).ln() / (t(lat_2) / t(lat_1)).ln()
+    };
+    (n, lat_1.cos() * t(lat_1).powf(n) / n)
+}
+
+impl Projection {
+    /// Project a position into map coordinates.
+    pub fn project(&self, position: GeoPosition) -> (f64, f64) {
+        match *self {
+            Projection::Mercator => position.to_map(),
+            Projection::Stereographic { center } => {
+                let (lat_0, lon_0) = (center.latitude.to_radians(), center.longitude.to_radians());
+                let (lat, lon) = (position.latitude.to_radians(), position.longitude.to_radians());
+                let k = 2. / (1. + lat_0.sin() * lat.sin() + lat_0.cos() * lat.cos() * (lon - lon_0).cos());
+                plane_to_map(
+                    k * lat.cos() * (lon - lon_0).sin(),
+                    k * (lat_0.cos() * lat.sin() - lat_0.sin() * lat.cos() * (lon - lon_0).cos()))
+            }
+            Projection::LambertConformalConic { origin, standard_parallels } => {
+                let (n, f) = lambert_constants(standard_parallels);
+                if n.abs() < 1e-10 {
+                    return Projection::Mercator.project(position);
+                }
+
+                let rho = |lat: f64| f / (PI / 4. + lat.to_radians() / 2.).tan().powf(n);
+                let (rho, rho_0) = (rho(position.latitude), rho(origin.latitude));
+                let theta = n * normalize_longitude(position.longitude - origin.longitude).to_radians();
+                plane_to_map(rho * theta.sin(), rho_0 - rho * theta.cos())
+            }
+        }
+    }
+
+    /// Turn map coordinates back into a position, the inverse of [`Projection::project`].
+    pub fn unproject(&self, map_position: (f64, f64)) -> GeoPosition {
+        match *self {
+            Projection::Mercator => map_to_geo(map_position.0, map_position.1).into(),
+            Projection::Stereographic { center } => {
+                let (x, y) = map_to_plane(map_position.0, map_position.1);
+                let rho = x.hypot(y);
+                if rho < 1e-15 {
+                    return center;
+                }
+
+                let (lat_0, lon_0) = (center.latitude.to_radians(), center.longitude.to_radians());
+                let c = 2. * (rho / 2.).atan();
+                let lat = (c.cos() * lat_0.sin() + y * c.sin() * lat_0.cos() / rho).asin();
+                let lon = lon_0 + (x * c.sin())
+                    .atan2(rho * lat_0.cos() * c.cos() - y * lat_0.sin() * c.sin());
+                GeoPosition::new(lat.to_degrees(), normalize_longitude(lon.to_degrees()))
+            }
+            Projection::LambertConformalConic { origin, standard_parallels } => {
+                let (n, f) = lambert_constants(standard_parallels);
+                if n.abs() < 1e-10 {
+                    return Projection::Mercator.unproject(map_position);
+                }
+
+                let (x, y) = map_to_plane(map_position.0, map_position.1);
+                let rho_0 = f / (PI / 4. + origin.latitude.to_radians() / 2.).tan().powf(n);
+                let rho = n.signum() * x.hypot(rho_0 - y);
+                let theta = (x * n.signum()).atan2((rho_0 - y) * n.signum());
+                let lat = 2. * (f / rho).powf(1. / n).atan() - PI / 2.;
+                GeoPosition::new(lat.to_degrees(), normalize_longitude(origin.longitude + (theta / n).to_degrees()))
+            }
+        }
+    }
+}
+
+/// The ways a [`GeoPosition`] can be written out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoordinateFormat {
+    /// Aurora sector files, such as `N060.02.03.005`.
+    Aurora,
+    /// The compact Aurora form, such as `N0600203005`.
+    Compact,
+    /// Signed decimal degrees, such as `60.034168`.
+    Decimal,
+    /// Degrees, minutes and seconds, such as `60°02'03.01"N`.
+    Dms,
+    /// Whole minutes as used in ICAO flight plans, such as `6002N`.
+    Icao,
+}
+
+fn parse_number(src: &str, what: &str) -> Result<f64, ParseError> {
+    src.parse()
+        .map_err(|_| ParseError::new(format!("invalid {}: {}", what, src)))
+}
+
+fn parse_coordinate(src: &str, pos: char, neg: char, limit: f64) -> Result<f64, ParseError> {
+    let is_decimal = match src.chars().next() {
+        Some('-') => true,
+        Some(x) => x.is_ascii_digit(),
+        None => false,
+    };
+
+    let value = if is_decimal {
+        parse_number(src, "decimal coordinate")?
+    } else {
+        let mut chars = src.chars();
+        let multiplier = match chars.next() {
+            Some(x) if x == pos => 1.0f64,
+            Some(x) if x == neg => -1.0f64,
+            Some(x) => return Err(ParseError::new(format!("unexpected prefix {}", x))),
+            None => return Err(ParseError::new("missing prefix character")),
+        };
+
+        let (degrees, minutes, seconds) = if src.contains('.') {
+            let mut parts = chars.as_str().splitn(3, '.');
+
+            let degrees = parse_number(parts.next().unwrap_or(""), "degrees")?;
+            let minutes = parts.next()
+                .ok_or_else(|| ParseError::new("missing minutes"))
+                .and_then(|v| parse_number(v, "minutes"))?;
+            let seconds = parts.next()
+                .ok_or_else(|| ParseError::new("missing seconds"))
+                .and_then(|v| parse_number(v, "seconds"))?;
+
+            (degrees, minutes, seconds)
+        } else if src.len() == 11 && src.is_ascii() {
+            let degrees = parse_number(&src[1..4], "degrees")?;
+            let minutes = parse_number(&src[4..6], "minutes")?;
+            let seconds = parse_number(&src[6..], "seconds")? / 1000.;
+            (degrees, minutes, seconds)
+        } else {
+            return Err(ParseError::new(format!("unsupported coordinate format: {}", src)));
+        };
+
+        if minutes >= 60. || seconds >= 60. {
+            return Err(ParseError::new(format!("minutes or seconds out of range: {}", src)));
+        }
+
+        (degrees + (minutes / 60f64) + (seconds / 3600f64)) * multiplier
+    };
+
+    if !(-limit..=limit).contains(&value) {
+        return Err(ParseError::new(format!("coordinate out of range: {}", src)));
+    }
+
+    Ok(value)
+}
+
+/// Parse a latitude in the Aurora, compact or decimal forms.
+pub fn parse_latitude(src: &str) -> Result<f64, ParseError> {
+    parse_coordinate(src, 'N', 'S', 90.)
+}
+
+/// Parse a longitude in the Aurora, compact or decimal forms.
+pub fn parse_longitude(src: &str) -> Result<f64, ParseError> {
+    parse_coordinate(src, 'E', 'W', 180.)
+}
+
+fn format_coordinate(value: f64, pos: char, neg: char, degree_digits: usize, format: CoordinateFormat) -> String {
+    let hemisphere = if value < 0. { neg } else { pos };
+    let value = value.abs();
+
+    match format {
+        CoordinateFormat::Aurora | CoordinateFormat::Compact => {
+            let millis = (value * 3_600_000.).round() as u64;
+            let (degrees, minutes, millis) = (millis / 3_600_000, (millis / 60_000) % 60, millis % 60_000);
+            if format == CoordinateFormat::Aurora {
+                format!("{}{:03}.{:02}.{:02}.{:03}", hemisphere, degrees, minutes, millis / 1000, millis % 1000)
+            } else {
+                format!("{}{:03}{:02}{:05}", hemisphere, degrees, minutes, millis)
+            }
+        }
+        CoordinateFormat::Decimal => {
+            let sign = if hemisphere == neg { "-" } else { "" };
+            format!("{}{:.6}", sign, value)
+        }
+        CoordinateFormat::Dms => {
+            let centis = (value * 360_000.).round() as u64;
+            let (degrees, minutes, centis) = (centis / 360_000, (centis / 6000) % 60, centis % 6000);
+            format!("{:0width$}°{:02}'{:02}.{:02}\"{}", degrees, minutes, centis / 100, centis % 100, hemisphere,
+                    width = degree_digits)
+        }
+        CoordinateFormat::Icao => {
+            let minutes = (value * 60.).round() as u64;
+            format!("{:0width$}{:02}{}", minutes / 60, minutes % 60, hemisphere, width = degree_digits)
+        }
+    }
+}
+
+/// A position on the Earth, in degrees.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeoPosition {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl GeoPosition {
+    pub fn new(latitude: f64, longitude: f64) -> GeoPosition {
+        GeoPosition {
+            latitude,
+            longitude,
+        }
+    }
+
+    /// Parse a latitude and longitude, each in any of the forms [`parse_latitude`] accepts.
+    pub fn parse(latitude: &str, longitude: &str) -> Result<GeoPosition, ParseError> {
+        Ok(GeoPosition::new(parse_latitude(latitude)?, parse_longitude(longitude)?))
+    }
+
+    /// Project this position onto the map, see [`geo_to_map`].
+    pub fn to_map(&self) -> (f64, f64) {
+        geo_to_map(self.latitude, self.longitude)
+    }
+
+    pub fn format_latitude(&self, format: CoordinateFormat) -> String {
+        format_coordinate(self.latitude, 'N', 'S', 2, format)
+    }
+
+    pub fn format_longitude(&self, format: CoordinateFormat) -> String {
+        format_coordinate(self.longitude, 'E', 'W', 3, format)
+    }
+
+    /// Write out both halves of this position, separated the way the format
+    /// usually is: `;` for Aurora, `, ` for decimal, a space for DMS and
+    /// nothing for ICAO.
+    pub fn format(&self, format: CoordinateFormat) -> String {
+        let separator = match format {
+            CoordinateFormat::Aurora | CoordinateFormat::Compact => ";",
+            CoordinateFormat::Decimal => ", ",
+            CoordinateFormat::Dms => " ",
+            CoordinateFormat::Icao => "",
+        };
+        format!("{}{}{}", self.format_latitude(format), separator, self.format_longitude(format))
+    }
+}
+
+impl Display for GeoPosition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.format(CoordinateFormat::Dms))
+    }
+}
+
+impl From<(f64, f64)> for GeoPosition {
+    fn from((latitude, longitude): (f64, f64)) -> GeoPosition {
+        GeoPosition::new(latitude, longitude)
+    }
+}
+
+impl From<GeoPosition> for (f64, f64) {
+    fn from(position: GeoPosition) -> (f64, f64) {
+        (position.latitude, position.longitude)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-6, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn test_great_circle() {
+        // A minute of arc along a meridian is about a nautical mile.
+        assert_close(distance_nm((0., 0.), (1., 0.)), EARTH_RADIUS_NM.to_radians());
+
+        assert_close(initial_bearing((0., 0.), (0., 90.)), 90.);
+        assert_close(initial_bearing((51.4775, -0.461389), (40.639722, -73.778889)).round(), 288.);
+        assert_close(final_bearing((51.4775, -0.461389), (40.639722, -73.778889)).round(), 231.);
+        assert_close(final_bearing((0., 0.), (0., -90.)), 270.);
+
+        let start = (51.4775, -0.461389);
+        let end = destination(start, 288., 3000.);
+        assert_close(distance_nm(start, end), 3000.);
+        assert_close(initial_bearing(start, end), 288.);
+        let (lat, long) = destination((0., 179.), 90., EARTH_RADIUS_NM.to_radians() * 2.);
+        assert_close(lat, 0.);
+        assert_close(long, -179.);
+
+        let offset = EARTH_RADIUS_NM.to_radians();
+        assert_close(cross_track_distance_nm((1., 45.), (0., 0.), (0., 90.)), -offset);
+        assert_close(cross_track_distance_nm((-1., 45.), (0., 0.), (0., 90.)), offset);
+
+        let (lat, long) = intermediate_point((0., 0.), (0., 90.), 0.5);
+        assert_close(lat, 0.);
+        assert_close(long, 45.);
+        assert_eq!(intermediate_point((10., 10.), (10., 10.), 0.5), (10., 10.));
+
+        let points = great_circle_points(start, (40.639722, -73.778889), 10);
+        assert_eq!(points.len(), 11);
+        assert_close(points[0].0, start.0);
+        assert_close(points[10].1, -73.778889);
+        // The great circle from London to New York passes well north of both.
+        assert!(points[5].0 > 52.);
+    }
+
+    #[test]
+    fn test_projections() {
+        let center = GeoPosition::new(60., 24.);
+        let projections = [
+            Projection::Mercator,
+            Projection::Stereographic { center },
+            Projection::LambertConformalConic { origin: center, standard_parallels: (55., 65.) },
+            Projection::LambertConformalConic { origin: center, standard_parallels: (60., 60.) },
+            Projection::LambertConformalConic { origin: GeoPosition::new(-35., 150.), standard_parallels: (-30., -40.) },
+        ];
+
+        for projection in projections {
+            for position in [center, GeoPosition::new(70., 30.), GeoPosition::new(-33.9, 151.2)] {
+                let unprojected = projection.unproject(projection.project(position));
+                assert_close(unprojected.latitude, position.latitude);
+                assert_close(unprojected.longitude, position.longitude);
+            }
+        }
+
+        let (x, y) = Projection::Stereographic { center }.project(center);
+        assert_close(x, 0.5);
+        assert_close(y, 0.5);
+
+        // A range ring stays circular around the centre of a stereographic projection.
+        let stereographic = Projection::Stereographic { center };
+        let radii = [0., 90., 180., 270.].iter()
+            .map(|bearing| {
+                let (x, y) = stereographic.project(destination(center.into(), *bearing, 100.).into());
+                (x - 0.5).hypot(y - 0.5)
+            })
+            .collect::<Vec<_>>();
+        for radius in radii.iter() {
+            assert!((radius - radii[0]).abs() < 1e-6 * radii[0]);
+        }
+
+        // North is up.
+        let (_, north) = Projection::LambertConformalConic { origin: center, standard_parallels: (55., 65.) }
+            .project(GeoPosition::new(61., 24.));
+        assert!(north < 0.5);
+
+        // Positions across the antimeridian from the origin stay beside it.
+        let pacific = Projection::LambertConformalConic {
+            origin: GeoPosition::new(50., 175.),
+            standard_parallels: (45., 55.),
+        };
+        let (east, _) = pacific.project(GeoPosition::new(50., -175.));
+        let (west, _) = pacific.project(GeoPosition::new(50., 165.));
+        assert_close(east - 0.5, 0.5 - west);
+        let unprojected = pacific.unproject((east, 0.5));
+        assert!(unprojected.longitude < -170.);
+
+        let json = serde_json::to_value(Projection::LambertConformalConic {
+            origin: center,
+            standard_parallels: (55., 65.),
+        }).unwrap();
+        assert_eq!(json["type"], "lambertConformalConic");
+        assert_eq!(json["standardParallels"][1], 65.);
+        assert_eq!(json["origin"]["latitude"], 60.);
+    }
+
+    #[test]
+    fn test_parse() {
+        let position = GeoPosition::parse("N060.02.03.005", "E023.12.56.000").unwrap();
+        assert_close(position.latitude, 60.034168);
+        assert_close(position.longitude, 23.215555);
+
+        let compact = GeoPosition::parse("N0600203005", "E0231256000").unwrap();
+        assert_close(compact.latitude, position.latitude);
+        assert_close(compact.longitude, position.longitude);
+
+        let decimal = GeoPosition::parse("-33.9461", "151.1772").unwrap();
+        assert_eq!(decimal, GeoPosition::new(-33.9461, 151.1772));
+
+        assert_close(parse_latitude("S033.56.46.000").unwrap(), -33.946111);
+        assert_close(parse_longitude("W000.27.41.000").unwrap(), -0.461389);
+
+        assert!(parse_latitude("E060.00.00.000").is_err());
+        assert!(parse_latitude("N091.00.00.000").is_err());
+        assert!(parse_latitude("N060.61.00.000").is_err());
+        assert!(parse_longitude("W0001").is_err());
+        assert!(parse_longitude("").is_err());
+    }
+
+    #[test]
+    fn test_format() {
+        let position = GeoPosition::parse("N051.28.39.000", "W000.27.41.500").unwrap();
+        assert_eq!(position.format(CoordinateFormat::Aurora), "N051.28.39.000;W000.27.41.500");
+        assert_eq!(position.format(CoordinateFormat::Compact), "N0512839000;W0002741500");
+        assert_eq!(position.format(CoordinateFormat::Decimal), "51.477500, -0.461528");
+        assert_eq!(position.format(CoordinateFormat::Dms), "51°28'39.00\"N 000°27'41.50\"W");
+        assert_eq!(position.format(CoordinateFormat::Icao), "5129N00028W");
+        assert_eq!(position.to_string(), position.format(CoordinateFormat::Dms));
+
+        let position = GeoPosition::new(51.5, -0.0833);
+        assert_eq!(position.format(CoordinateFormat::Icao), "5130N00005W");
+
+        // Rounding up carries into the minutes and degrees.
+        let position = GeoPosition::new(59.99999999, 0.);
+        assert_eq!(position.format_latitude(CoordinateFormat::Aurora), "N060.00.00.000");
+        assert_eq!(position.format_latitude(CoordinateFormat::Icao), "6000N");
+
+        for format in [CoordinateFormat::Aurora, CoordinateFormat::Compact, CoordinateFormat::Decimal] {
+            let parsed = GeoPosition::parse(
+                &position.format_latitude(format),
+                &position.format_longitude(format)).unwrap();
+            assert_close(parsed.latitude, position.latitude);
+            assert_close(parsed.longitude, position.longitude);
+        }
+    }
+}
@@ -0,0 +1,629 @@
+//! Flight plans, in the ICAO `FPL` message format.
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::domain::parsing::{serde_via_string, ParseError};
+
+/// Item 8a, the flight rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FlightRules {
+    Ifr,
+    Vfr,
+    /// IFR first, then VFR, written as `Y`.
+    IfrThenVfr,
+    /// VFR first, then IFR, written as `Z`.
+    VfrThenIfr,
+}
+
+impl FlightRules {
+    fn from_char(c: char) -> Option<FlightRules> {
+        match c {
+            'I' => Some(FlightRules::Ifr),
+            'V' => Some(FlightRules::Vfr),
+            'Y' => Some(FlightRules::IfrThenVfr),
+            'Z' => Some(FlightRules::VfrThenIfr),
+            _ => None,
+        }
+    }
+
+    fn as_char(&self) -> char {
+        match self {
+            FlightRules::Ifr => 'I',
+            FlightRules::Vfr => 'V',
+            FlightRules::IfrThenVfr => 'Y',
+            FlightRules::VfrThenIfr => 'Z',
+        }
+    }
+}
+
+/// Item 8b, the type of flight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FlightType {
+    Scheduled,
+    NonScheduled,
+    GeneralAviation,
+    Military,
+    Other,
+}
+
+impl FlightType {
+    fn from_char(c: char) -> Option<FlightType> {
+        match c {
+            'S' => Some(FlightType::Scheduled),
+            'N' => Some(FlightType::NonScheduled),
+            'G' => Some(FlightType::GeneralAviation),
+            'M' => Some(FlightType::Military),
+            'X' => Some(FlightType::Other),
+            _ => None,
+        }
+    }
+
+    fn as_char(&self) -> char {
+        match self {
+            FlightType::Scheduled => 'S',
+            FlightType::NonScheduled => 'N',
+            FlightType::GeneralAviation => 'G',
+            FlightType::Military => 'M',
+            FlightType::Other => 'X',
+        }
+    }
+}
+
+/// Item 9c, the wake turbulence category.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum WakeCategory {
+    Light,
+    Medium,
+    Heavy,
+    /// The A380 and similar, written as `J`.
+    Super,
+}
+
+impl WakeCategory {
+    fn from_char(c: char) -> Option<WakeCategory> {
+        match c {
+            'L' => Some(WakeCategory::Light),
+            'M' => Some(WakeCategory::Medium),
+            'H' => Some(WakeCategory::Heavy),
+            'J' => Some(WakeCategory::Super),
+            _ => None,
+        }
+    }
+
+    fn as_char(&self) -> char {
+        match self {
+            WakeCategory::Light => 'L',
+            WakeCategory::Medium => 'M',
+            WakeCategory::Heavy => 'H',
+            WakeCategory::Super => 'J',
+        }
+    }
+}
+
+/// The cruising speed from item 15, such as `N0450` or `M082`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CruiseSpeed {
+    Knots(u16),
+    KilometresPerHour(u16),
+    /// In hundredths of Mach.
+    Mach(u16),
+}
+
+/// The cruising level from item 15, such as `F350` or `VFR`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CruiseLevel {
+    /// In hundreds of feet.
+    FlightLevel(u16),
+    /// In hundreds of feet.
+    Altitude(u16),
+    /// A metric level in tens of metres, written with `S`.
+    MetricLevel(u16),
+    /// A metric altitude in tens of metres, written with `M`.
+    MetricAltitude(u16),
+    Vfr,
+}
+
+fn parse_digits(src: &str, len: usize, what: &str) -> Result<u16, ParseError> {
+    if src.len() != len || !src.chars().all(|c| c.is_ascii_digit()) {
+        return Err(ParseError::new(format!("invalid {}: {}", what, src)));
+    }
+    src.parse()
+        .map_err(|_| ParseError::new(format!("invalid {}: {}", what, src)))
+}
+
+/// Parse a duration written as `HHMM`, in minutes.
+fn parse_duration(src: &str, what: &str) -> Result<u32, ParseError> {
+    let value = parse_digits(src, 4, what)? as u32;
+    if value % 100 >= 60 {
+        return Err(ParseError::new(format!("invalid {}: {}", what, src)));
+    }
+    Ok(value / 100 * 60 + value % 100)
+}
+
+/// Parse a time of day written as `HHMM`, in minutes after midnight.
+fn parse_time(src: &str, what: &str) -> Result<u32, ParseError> {
+    let minutes = parse_duration(src, what)?;
+    if minutes >= 24 * 60 {
+        return Err(ParseError::new(format!("invalid {}: {}", what, src)));
+    }
+    Ok(minutes)
+}
+
+fn format_time(minutes: u32) -> String {
+    format!("{:02}{:02}", minutes / 60, minutes % 60)
+}
+
+fn parse_aerodrome(src: &str, what: &str) -> Result<String, ParseError> {
+    if src.len() != 4 || !src.chars().all(|c| c.is_ascii_uppercase()) {
+        return Err(ParseError::new(format!("invalid {}: {}", what, src)));
+    }
+    Ok(src.to_owned())
+}
+
+impl FromStr for CruiseSpeed {
+    type Err = ParseError;
+
+    fn from_str(src: &str) -> Result<CruiseSpeed, ParseError> {
+        let (unit, value) = src.split_at(src.chars().next().map_or(0, char::len_utf8));
+        match unit {
+            "N" => Ok(CruiseSpeed::Knots(parse_digits(value, 4, "cruise speed")?)),
+            "K" => Ok(CruiseSpeed::KilometresPerHour(parse_digits(value, 4, "cruise speed")?)),
+            "M" => Ok(CruiseSpeed::Mach(parse_digits(value, 3, "cruise speed")?)),
+            _ => Err(ParseError::new(format!("invalid cruise speed: {}", src))),
+        }
+    }
+}
+
+impl Display for CruiseSpeed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            CruiseSpeed::Knots(value) => write!(f, "N{:04}", value),
+            CruiseSpeed::KilometresPerHour(value) => write!(f, "K{:04}", value),
+            CruiseSpeed::Mach(value) => write!(f, "M{:03}", value),
+        }
+    }
+}
+
+impl FromStr for CruiseLevel {
+    type Err = ParseError;
+
+    fn from_str(src: &str) -> Result<CruiseLevel, ParseError> {
+        if src == "VFR" {
+            return Ok(CruiseLevel::Vfr);
+        }
+
+        let (unit, value) = src.split_at(src.chars().next().map_or(0, char::len_utf8));
+        match unit {
+            "F" => Ok(CruiseLevel::FlightLevel(parse_digits(value, 3, "cruise level")?)),
+            "A" => Ok(CruiseLevel::Altitude(parse_digits(value, 3, "cruise level")?)),
+            "S" => Ok(CruiseLevel::MetricLevel(parse_digits(value, 4, "cruise level")?)),
+            "M" => Ok(CruiseLevel::MetricAltitude(parse_digits(value, 4, "cruise level")?)),
+            _ => Err(ParseError::new(format!("invalid cruise level: {}", src))),
+        }
+    }
+}
+
+impl Display for CruiseLevel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            CruiseLevel::FlightLevel(value) => write!(f, "F{:03}", value),
+            CruiseLevel::Altitude(value) => write!(f, "A{:03}", value),
+            CruiseLevel::MetricLevel(value) => write!(f, "S{:04}", value),
+            CruiseLevel::MetricAltitude(value) => write!(f, "M{:04}", value),
+            CruiseLevel::Vfr => write!(f, "VFR"),
+        }
+    }
+}
+
+serde_via_string!(CruiseSpeed);
+serde_via_string!(CruiseLevel);
+
+/// Parse the start of item 15, such as `N0450F350`.
+pub fn parse_speed_and_level(src: &str) -> Result<(CruiseSpeed, CruiseLevel), ParseError> {
+    let split = if src.starts_with('M') { 4 } else { 5 };
+    if src.len() <= split || !src.is_char_boundary(split) {
+        return Err(ParseError::new(format!("invalid cruise speed and level: {}", src)));
+    }
+    let (speed, level) = src.split_at(split);
+    Ok((speed.parse()?, level.parse()?))
+}
+
+/// The indicators which can start an entry in item 18.
+const OTHER_INFORMATION_INDICATORS: &[&str] = &[
+    "STS", "PBN", "NAV", "COM", "DAT", "SUR", "DEP", "DEST", "DOF", "REG", "EET", "SEL", "TYP",
+    "CODE", "DLE", "OPR", "ORGN", "PER", "ALTN", "RALT", "TALT", "RIF", "RMK",
+];
+
+/// The indicators which can start an entry in item 19.
+const SUPPLEMENTARY_INFORMATION_INDICATORS: &[char] = &['E', 'P', 'R', 'S', 'J', 'D', 'A', 'N', 'C'];
+
+/// Split the end of a flight plan into items 18 and 19.
+///
+/// Item 18 is free text which may contain hyphens, as in `RMK/TCAS-EQUIPPED`,
+/// so only a hyphen followed by an item 19 indicator such as `E/` ends it.
+fn split_supplementary_information(src: &str) -> (&str, Option<&str>) {
+    let starts_item_19 = |rest: &str| {
+        let mut chars = rest.trim_start().chars();
+        matches!((chars.next(), chars.next()), (Some(c), Some('/')) if SUPPLEMENTARY_INFORMATION_INDICATORS.contains(&c))
+    };
+
+    src.match_indices('-')
+        .map(|(i, _)| i)
+        .find(|&i| starts_item_19(&src[i + 1..]))
+        .map_or((src, None), |i| (&src[..i], Some(src[i + 1..].trim())))
+}
+
+/// An entry in item 18, such as `RMK/TCAS`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OtherInformation {
+    pub indicator: String,
+    pub text: String,
+}
+
+/// Parse item 18 into its entries, where `0` means there are none.
+pub fn parse_other_information(src: &str) -> Result<Vec<OtherInformation>, ParseError> {
+    let src = src.trim();
+    if src == "0" || src.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut entries: Vec<OtherInformation> = Vec::new();
+    for word in src.split_whitespace() {
+        let indicator = word.split_once('/')
+            .map(|(indicator, _)| indicator)
+            .filter(|indicator| OTHER_INFORMATION_INDICATORS.contains(indicator));
+
+        match (indicator, entries.last_mut()) {
+            (Some(indicator), _) => entries.push(OtherInformation {
+                indicator: indicator.to_owned(),
+                text: word[indicator.len() + 1..].to_owned(),
+            }),
+            (None, Some(last)) => {
+                if !last.text.is_empty() {
+                    last.text.push(' ');
+                }
+                last.text.push_str(word);
+            }
+            (None, None) => return Err(ParseError::new(format!("invalid other information: {}", src))),
+        }
+    }
+
+    Ok(entries)
+}
+
+/// A flight plan, with the items of an ICAO `FPL` message.
+///
+/// Times and durations are in minutes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FlightPlan {
+    /// Item 7.
+    pub callsign: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ssr_code: Option<String>,
+    /// Item 8.
+    pub flight_rules: FlightRules,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flight_type: Option<FlightType>,
+    /// Item 9.
+    pub aircraft_count: u32,
+    pub aircraft_type: String,
+    pub wake_category: WakeCategory,
+    /// Item 10, the equipment and surveillance capability codes.
+    pub equipment: String,
+    pub surveillance: String,
+    /// Item 13.
+    pub departure: String,
+    pub departure_time: u32,
+    /// Item 15.
+    pub cruise_speed: CruiseSpeed,
+    pub cruise_level: CruiseLevel,
+    pub route: String,
+    /// Item 16.
+    pub destination: String,
+    pub eet: u32,
+    pub alternates: Vec<String>,
+    /// Item 18.
+    pub other_information: Vec<OtherInformation>,
+    /// Item 19, kept as written.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub supplementary_information: Option<String>,
+}
+
+impl FlightPlan {
+    /// Parse an ICAO `FPL` message, such as
+    /// `(FPL-BAW123-IS -B744/H-SDE3FGHIJ3J5M1RWXY/LB1D1 -EGLL1200 ...)`.
+    pub fn parse(src: &str) -> Result<FlightPlan, ParseError> {
+        src.parse()
+    }
+
+    /// The text of the first item 18 entry with an indicator, such as `RMK`.
+    pub fn other_information(&self, indicator: &str) -> Option<&str> {
+        self.other_information.iter()
+            .find(|entry| entry.indicator == indicator)
+            .map(|entry| entry.text.as_str())
+    }
+}
+
+impl FromStr for FlightPlan {
+    type Err = ParseError;
+
+    fn from_str(src: &str) -> Result<FlightPlan, ParseError> {
+        let src = src.trim();
+        let src = src.strip_prefix('(')
+            .and_then(|src| src.strip_suffix(')'))
+            .unwrap_or(src);
+
+        // Items up to 16 can't contain hyphens, but items 18 and 19 are split
+        // separately as item 18 may.
+        let mut items = src.splitn(9, '-').map(str::trim);
+        let mut next = |what: &str| items.next()
+            .ok_or_else(|| ParseError::new(format!("missing {}", what)));
+
+        let message_type = next("message type")?;
+        if message_type != "FPL" {
+            return Err(ParseError::new(format!("not a flight plan: {}", message_type)));
+        }
+
+        let identification = next("aircraft identification")?;
+        let (callsign, ssr_code) = match identification.split_once('/') {
+            Some((callsign, code)) => (callsign, Some(code.to_owned())),
+            None => (identification, None),
+        };
+        if callsign.is_empty() || callsign.len() > 7 || !callsign.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(ParseError::new(format!("invalid aircraft identification: {}", callsign)));
+        }
+
+        let rules = next("flight rules")?;
+        let mut chars = rules.chars();
+        let flight_rules = chars.next().and_then(FlightRules::from_char);
+        let flight_type = chars.next().map(FlightType::from_char);
+        let (flight_rules, flight_type) = match (flight_rules, flight_type, chars.next()) {
+            (Some(rules), None, None) => (rules, None),
+            (Some(rules), Some(Some(kind)), None) => (rules, Some(kind)),
+            _ => return Err(ParseError::new(format!("invalid flight rules: {}", rules))),
+        };
+
+        let aircraft = next("aircraft type")?;
+        let (aircraft, wake) = aircraft.split_once('/')
+            .ok_or_else(|| ParseError::new(format!("missing wake category: {}", aircraft)))?;
+        let wake_category = Some(wake).filter(|w| w.len() == 1)
+            .and_then(|w| w.chars().next())
+            .and_then(WakeCategory::from_char)
+            .ok_or_else(|| ParseError::new(format!("invalid wake category: {}", wake)))?;
+        let count_len = aircraft.find(|c: char| !c.is_ascii_digit()).unwrap_or(aircraft.len());
+        let (count, aircraft_type) = aircraft.split_at(count_len);
+        let aircraft_count = if count.is_empty() {
+            1
+        } else {
+            count.parse()
+                .map_err(|_| ParseError::new(format!("invalid number of aircraft: {}", count)))?
+        };
+        if aircraft_type.is_empty() || aircraft_type.len() > 4 {
+            return Err(ParseError::new(format!("invalid aircraft type: {}", aircraft_type)));
+        }
+
+        let equipment = next("equipment")?;
+        let (equipment, surveillance) = equipment.split_once('/')
+            .ok_or_else(|| ParseError::new(format!("missing surveillance equipment: {}", equipment)))?;
+
+        let departure = next("departure")?;
+        if departure.len() != 8 || !departure.is_char_boundary(4) {
+            return Err(ParseError::new(format!("invalid departure: {}", departure)));
+        }
+        let departure_time = parse_time(&departure[4..], "departure time")?;
+        let departure = parse_aerodrome(&departure[..4], "departure aerodrome")?;
+
+        let route = next("route")?;
+        let (speed_and_level, route) = route.split_once(char::is_whitespace).unwrap_or((route, ""));
+        let (cruise_speed, cruise_level) = parse_speed_and_level(speed_and_level)?;
+        let route = route.split_whitespace().collect::<Vec<_>>().join(" ");
+
+        let destination = next("destination")?;
+        let mut words = destination.split_whitespace();
+        let destination = words.next().unwrap_or_default();
+        if destination.len() != 8 || !destination.is_char_boundary(4) {
+            return Err(ParseError::new(format!("invalid destination: {}", destination)));
+        }
+        let eet = parse_duration(&destination[4..], "total EET")?;
+        let destination = parse_aerodrome(&destination[..4], "destination aerodrome")?;
+        let alternates = words.map(|w| parse_aerodrome(w, "alternate aerodrome"))
+            .collect::<Result<Vec<_>, _>>()?;
+        if alternates.len() > 2 {
+            return Err(ParseError::new("more than two alternate aerodromes"));
+        }
+
+        let (other_information, supplementary_information) = match items.next() {
+            Some(rest) => {
+                let (other, supplementary) = split_supplementary_information(rest);
+                (parse_other_information(other)?, supplementary.map(str::to_owned))
+            }
+            None => (Vec::new(), None),
+        };
+
+        Ok(FlightPlan {
+            callsign: callsign.to_owned(),
+            ssr_code,
+            flight_rules,
+            flight_type,
+            aircraft_count,
+            aircraft_type: aircraft_type.to_owned(),
+            wake_category,
+            equipment: equipment.to_owned(),
+            surveillance: surveillance.to_owned(),
+            departure,
+            departure_time,
+            cruise_speed,
+            cruise_level,
+            route,
+            destination,
+            eet,
+            alternates,
+            other_information,
+            supplementary_information,
+        })
+    }
+}
+
+impl Display for FlightPlan {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "(FPL-{}", self.callsign)?;
+        if let Some(code) = &self.ssr_code {
+            write!(f, "/{}", code)?;
+        }
+        write!(f, "-{}", self.flight_rules.as_char())?;
+        if let Some(kind) = self.flight_type {
+            write!(f, "{}", kind.as_char())?;
+        }
+
+        writeln!(f)?;
+        write!(f, "-")?;
+        if self.aircraft_count != 1 {
+            write!(f, "{}", self.aircraft_count)?;
+        }
+        writeln!(f, "{}/{}-{}/{}", self.aircraft_type, self.wake_category.as_char(),
+                 self.equipment, self.surveillance)?;
+
+        writeln!(f, "-{}{}", self.departure, format_time(self.departure_time))?;
+
+        write!(f, "-{}{}", self.cruise_speed, self.cruise_level)?;
+        if !self.route.is_empty() {
+            write!(f, " {}", self.route)?;
+        }
+        writeln!(f)?;
+
+        write!(f, "-{}{}", self.destination, format_time(self.eet))?;
+        for alternate in self.alternates.iter() {
+            write!(f, " {}", alternate)?;
+        }
+        writeln!(f)?;
+
+        write!(f, "-")?;
+        if self.other_information.is_empty() {
+            write!(f, "0")?;
+        }
+        for (i, entry) in self.other_information.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}/{}", entry.indicator, entry.text)?;
+        }
+
+        if let Some(supplementary) = &self.supplementary_information {
+            writeln!(f)?;
+            write!(f, "-{}", supplementary)?;
+        }
+        write!(f, ")")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FPL: &str = "(FPL-BAW123-IS
+-B744/H-SDE3FGHIJ3J5M1RWXY/LB1D1
+-EGLL1200
+-N0490F350 DVR UL9 KONAN UL607
+-KJFK0730 KBOS KEWR
+-PBN/A1B1C1D1 DOF/240101 REG/GBNLI RMK/TCAS EQUIPPED)";
+
+    #[test]
+    fn test_parse() {
+        let plan = FlightPlan::parse(FPL).unwrap();
+        assert_eq!(plan.callsign, "BAW123");
+        assert_eq!(plan.ssr_code, None);
+        assert_eq!(plan.flight_rules, FlightRules::Ifr);
+        assert_eq!(plan.flight_type, Some(FlightType::Scheduled));
+        assert_eq!(plan.aircraft_count, 1);
+        assert_eq!(plan.aircraft_type, "B744");
+        assert_eq!(plan.wake_category, WakeCategory::Heavy);
+        assert_eq!(plan.equipment, "SDE3FGHIJ3J5M1RWXY");
+        assert_eq!(plan.surveillance, "LB1D1");
+        assert_eq!(plan.departure, "EGLL");
+        assert_eq!(plan.departure_time, 12 * 60);
+        assert_eq!(plan.cruise_speed, CruiseSpeed::Knots(490));
+        assert_eq!(plan.cruise_level, CruiseLevel::FlightLevel(350));
+        assert_eq!(plan.route, "DVR UL9 KONAN UL607");
+        assert_eq!(plan.destination, "KJFK");
+        assert_eq!(plan.eet, 7 * 60 + 30);
+        assert_eq!(plan.alternates, vec!["KBOS", "KEWR"]);
+        assert_eq!(plan.other_information.len(), 4);
+        assert_eq!(plan.other_information("DOF"), Some("240101"));
+        assert_eq!(plan.other_information("RMK"), Some("TCAS EQUIPPED"));
+        assert_eq!(plan.supplementary_information, None);
+
+        let plan = FlightPlan::parse("(FPL-RRR01/A1234-VM-2F16/M-S/C-EGXC0930-M082S1130 DCT-EGXC0100-0-E/0200 P/2)").unwrap();
+        assert_eq!(plan.ssr_code.as_deref(), Some("A1234"));
+        assert_eq!(plan.flight_rules, FlightRules::Vfr);
+        assert_eq!(plan.flight_type, Some(FlightType::Military));
+        assert_eq!(plan.aircraft_count, 2);
+        assert_eq!(plan.aircraft_type, "F16");
+        assert_eq!(plan.cruise_speed, CruiseSpeed::Mach(82));
+        assert_eq!(plan.cruise_level, CruiseLevel::MetricLevel(1130));
+        assert!(plan.alternates.is_empty());
+        assert!(plan.other_information.is_empty());
+        assert_eq!(plan.supplementary_information.as_deref(), Some("E/0200 P/2"));
+
+        let plan = FlightPlan::parse("(FPL-BAW123-IS-B744/H-S/C-EGLL2330-N0490F350 DCT-KJFK2615\
+            -RMK/TCAS-EQUIPPED NO-RVSM-E/2800 -P/TBN)").unwrap();
+        assert_eq!(plan.departure_time, 23 * 60 + 30);
+        assert_eq!(plan.eet, 26 * 60 + 15);
+        assert_eq!(plan.other_information("RMK"), Some("TCAS-EQUIPPED NO-RVSM"));
+        assert_eq!(plan.supplementary_information.as_deref(), Some("E/2800 -P/TBN"));
+
+        for src in [
+            "(ABC-BAW123-IS-B744/H-S/C-EGLL1200-N0490F350 DCT-KJFK0730)",
+            "(FPL-BAW123-Q-B744/H-S/C-EGLL1200-N0490F350 DCT-KJFK0730)",
+            "(FPL-BAW123-IS-B744/Q-S/C-EGLL1200-N0490F350 DCT-KJFK0730)",
+            "(FPL-BAW123-IS-B744/H-SC-EGLL1200-N0490F350 DCT-KJFK0730)",
+            "(FPL-BAW123-IS-B744/H-S/C-EGLL1260-N0490F350 DCT-KJFK0730)",
+            "(FPL-BAW123-IS-B744/H-S/C-EGLL2400-N0490F350 DCT-KJFK0730)",
+            "(FPL-BAW123-IS-B744/H-S/C-EGLL1200-X0490F350 DCT-KJFK0730)",
+            "(FPL-BAW123-IS-B744/H-S/C-EGLL1200-N0490F350 DCT-KJFK)",
+            "(FPL-BAW123-IS-B744/H-S/C-EGLL1200-N0490F350 DCT-KJFK0730 A B C)",
+            "(FPL-BAW123-IS-B744/H-S/C-EGLL1200-N0490F350 DCT)",
+        ] {
+            assert!(FlightPlan::parse(src).is_err(), "{}", src);
+        }
+    }
+
+    #[test]
+    fn test_fields() {
+        assert_eq!(parse_speed_and_level("K0830S1130").unwrap(),
+                   (CruiseSpeed::KilometresPerHour(830), CruiseLevel::MetricLevel(1130)));
+        assert_eq!(parse_speed_and_level("N0120VFR").unwrap(), (CruiseSpeed::Knots(120), CruiseLevel::Vfr));
+        assert_eq!(parse_speed_and_level("N0120A045").unwrap().1, CruiseLevel::Altitude(45));
+        assert!(parse_speed_and_level("N120F350").is_err());
+        assert!(parse_speed_and_level("M08").is_err());
+
+        assert_eq!(parse_other_information("0").unwrap(), vec![]);
+        assert_eq!(parse_other_information("STS/HOSP RMK/A/B TEST").unwrap(), vec![
+            OtherInformation { indicator: "STS".into(), text: "HOSP".into() },
+            OtherInformation { indicator: "RMK".into(), text: "A/B TEST".into() },
+        ]);
+        assert!(parse_other_information("TCAS").is_err());
+    }
+
+    #[test]
+    fn test_format() {
+        let plan = FlightPlan::parse(FPL).unwrap();
+        assert_eq!(plan.to_string(), FPL);
+
+        let src = "(FPL-RRR01/A1234-VM\n-2F16/M-S/C\n-EGXC0930\n-M082S1130\n-EGXC0100\n-0\n-E/0200 P/2)";
+        assert_eq!(FlightPlan::parse(src).unwrap().to_string(), src);
+
+        let json = serde_json::to_value(&plan).unwrap();
+        assert_eq!(json["flightRules"], "ifr");
+        assert_eq!(json["cruiseSpeed"], "N0490");
+        assert_eq!(json["cruiseLevel"], "F350");
+        assert_eq!(json["otherInformation"][3]["text"], "TCAS EQUIPPED");
+        assert_eq!(serde_json::from_value::<FlightPlan>(json).unwrap(), plan);
+    }
+}
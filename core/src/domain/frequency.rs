@@ -0,0 +1,233 @@
+//! Radio frequencies, for both communication and navigation.
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use crate::domain::parsing::{serde_via_string, ParseError};
+
+/// The spacing between neighbouring VHF communication channels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelSpacing {
+    Khz25,
+    Khz8_33,
+}
+
+/// A radio frequency.
+///
+/// Frequencies are written out the way they're usually given: communication
+/// channels such as `118.705` and navaids such as `114.40` in MHz, and NDBs
+/// such as `338.5` in kHz. They're serialized the same way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Frequency {
+    /// A VHF communication channel, by its name in kHz, such as `118705` for
+    /// `118.705`.
+    ///
+    /// With 8.33 kHz spacing the name isn't the frequency actually used, so
+    /// see [`Frequency::hertz`] for that.
+    Comm(u32),
+    /// A VHF navigation frequency for VORs and ILSs, in kHz.
+    Nav(u32),
+    /// An LF or MF frequency for NDBs, in Hz.
+    LowFrequency(u32),
+}
+
+const NAV_RANGE: std::ops::Range<u32> = 108_000..118_000;
+const COMM_RANGE: std::ops::Range<u32> = 118_000..137_000;
+const LOW_FREQUENCY_RANGE: std::ops::Range<u32> = 150_000..1_800_000;
+
+impl Frequency {
+    /// A communication channel from FSD, which gives them in kHz above 100 MHz.
+    pub fn from_fsd(value: u32) -> Result<Frequency, ParseError> {
+        let channel = value.checked_add(100_000)
+            .ok_or_else(|| ParseError::new(format!("FSD frequency out of range: {}", value)))?;
+        let frequency = Frequency::Comm(channel);
+        frequency.validate()?;
+        Ok(frequency)
+    }
+
+    /// The channel as FSD gives it, in kHz above 100 MHz.
+    pub fn to_fsd(&self) -> Option<u32> {
+        match self {
+            Frequency::Comm(channel) => channel.checked_sub(100_000),
+            _ => None,
+        }
+    }
+
+    /// The spacing of a communication channel.
+    pub fn spacing(&self) -> Option<ChannelSpacing> {
+        match self {
+            Frequency::Comm(channel) if channel % 25 == 0 => Some(ChannelSpacing::Khz25),
+            Frequency::Comm(_) => Some(ChannelSpacing::Khz8_33),
+            _ => None,
+        }
+    }
+
+    /// The frequency actually transmitted on, in Hz.
+    pub fn hertz(&self) -> u64 {
+        match *self {
+            Frequency::Comm(channel) => {
+                // Each 25 kHz block holds three 8.33 kHz channels, named
+                // `.x05`, `.x10` and `.x15` from the bottom of the block.
+                let block = channel - channel % 25;
+                let offset = match channel % 25 {
+                    0 | 5 => 0,
+                    10 => 8_333,
+                    _ => 16_667,
+                };
+                block as u64 * 1000 + offset
+            }
+            Frequency::Nav(khz) => khz as u64 * 1000,
+            Frequency::LowFrequency(hz) => hz as u64,
+        }
+    }
+
+    /// The frequency in MHz, mostly for display and calculations.
+    pub fn megahertz(&self) -> f64 {
+        self.hertz() as f64 / 1e6
+    }
+
+    fn validate(&self) -> Result<(), ParseError> {
+        match *self {
+            Frequency::Comm(channel) => {
+                if !COMM_RANGE.contains(&channel) {
+                    return Err(ParseError::new(format!("communication channel out of range: {}", self)));
+                }
+                if !matches!(channel % 25, 0 | 5 | 10 | 15) {
+                    return Err(ParseError::new(format!("invalid channel: {}", self)));
+                }
+            }
+            Frequency::Nav(khz) => {
+                if !NAV_RANGE.contains(&khz) {
+                    return Err(ParseError::new(format!("navigation frequency out of range: {}", self)));
+                }
+                if khz % 50 != 0 {
+                    return Err(ParseError::new(format!("navigation frequency not on a 50 kHz step: {}", self)));
+                }
+            }
+            Frequency::LowFrequency(hz) => {
+                if !LOW_FREQUENCY_RANGE.contains(&hz) {
+                    return Err(ParseError::new(format!("NDB frequency out of range: {}", self)));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Frequency {
+    type Err = ParseError;
+
+    fn from_str(src: &str) -> Result<Frequency, ParseError> {
+        let invalid = || ParseError::new(format!("invalid frequency: {}", src));
+
+        let (whole, fraction) = src.split_once('.').unwrap_or((src, ""));
+        if whole.is_empty() || fraction.len() > 3
+            || !whole.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit()) {
+            return Err(invalid());
+        }
+
+        // In thousandths of the unit given, which is kHz for MHz and Hz for kHz.
+        let whole = whole.parse::<u32>().map_err(|_| invalid())?;
+        let thousandths = whole.checked_mul(1000)
+            .and_then(|w| w.checked_add(format!("{:0<3}", fraction).parse::<u32>().ok()?))
+            .ok_or_else(invalid)?;
+
+        let frequency = if NAV_RANGE.contains(&thousandths) {
+            Frequency::Nav(thousandths)
+        } else if COMM_RANGE.contains(&thousandths) {
+            // 25 kHz channels are often shortened, so `118.02` is `118.025`.
+            if fraction.len() == 2 && (fraction.ends_with('2') || fraction.ends_with('7')) {
+                Frequency::Comm(thousandths + 5)
+            } else {
+                Frequency::Comm(thousandths)
+            }
+        } else if LOW_FREQUENCY_RANGE.contains(&thousandths) {
+            Frequency::LowFrequency(thousandths)
+        } else {
+            return Err(ParseError::new(format!("frequency out of range: {}", src)));
+        };
+
+        frequency.validate()?;
+        Ok(frequency)
+    }
+}
+
+impl Display for Frequency {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match *self {
+            Frequency::Comm(channel) => write!(f, "{}.{:03}", channel / 1000, channel % 1000),
+            Frequency::Nav(khz) => write!(f, "{}.{:02}", khz / 1000, khz % 1000 / 10),
+            Frequency::LowFrequency(hz) if hz % 1000 == 0 => write!(f, "{}", hz / 1000),
+            Frequency::LowFrequency(hz) => {
+                let fraction = format!("{:03}", hz % 1000);
+                write!(f, "{}.{}", hz / 1000, fraction.trim_end_matches('0'))
+            }
+        }
+    }
+}
+
+serde_via_string!(Frequency);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(src: &str) -> Frequency {
+        src.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("118.7"), Frequency::Comm(118_700));
+        assert_eq!(parse("118.700"), Frequency::Comm(118_700));
+        assert_eq!(parse("121.725"), Frequency::Comm(121_725));
+        assert_eq!(parse("121.72"), Frequency::Comm(121_725));
+        assert_eq!(parse("132.005"), Frequency::Comm(132_005));
+        assert_eq!(parse("114.4"), Frequency::Nav(114_400));
+        assert_eq!(parse("109.35"), Frequency::Nav(109_350));
+        assert_eq!(parse("375"), Frequency::LowFrequency(375_000));
+        assert_eq!(parse("338.5"), Frequency::LowFrequency(338_500));
+
+        assert!("".parse::<Frequency>().is_err());
+        assert!("abc".parse::<Frequency>().is_err());
+        assert!("-118.7".parse::<Frequency>().is_err());
+        assert!("118.7000".parse::<Frequency>().is_err());
+        assert!("118.720".parse::<Frequency>().is_err());
+        assert!("114.42".parse::<Frequency>().is_err());
+        assert!("99.9".parse::<Frequency>().is_err());
+        assert!("4000".parse::<Frequency>().is_err());
+    }
+
+    #[test]
+    fn test_format() {
+        for src in ["118.700", "121.725", "132.005", "114.40", "109.35", "375", "338.5"] {
+            assert_eq!(parse(src).to_string(), src);
+        }
+
+        let json = serde_json::to_string(&parse("118.7")).unwrap();
+        assert_eq!(json, "\"118.700\"");
+        assert_eq!(serde_json::from_str::<Frequency>(&json).unwrap(), Frequency::Comm(118_700));
+        assert!(serde_json::from_str::<Frequency>("\"1.0\"").is_err());
+    }
+
+    #[test]
+    fn test_channels() {
+        assert_eq!(parse("118.700").spacing(), Some(ChannelSpacing::Khz25));
+        assert_eq!(parse("118.705").spacing(), Some(ChannelSpacing::Khz8_33));
+        assert_eq!(parse("114.40").spacing(), None);
+
+        assert_eq!(parse("118.700").hertz(), 118_700_000);
+        assert_eq!(parse("118.705").hertz(), 118_700_000);
+        assert_eq!(parse("118.710").hertz(), 118_708_333);
+        assert_eq!(parse("118.715").hertz(), 118_716_667);
+        assert_eq!(parse("118.730").hertz(), 118_725_000);
+        assert_eq!(parse("338.5").hertz(), 338_500);
+
+        assert_eq!(Frequency::from_fsd(18700), Ok(Frequency::Comm(118_700)));
+        assert_eq!(parse("118.705").to_fsd(), Some(18705));
+        assert!(Frequency::from_fsd(18720).is_err());
+        assert_eq!(parse("114.40").to_fsd(), None);
+        assert!(Frequency::from_fsd(u32::MAX).is_err());
+        assert_eq!(Frequency::Comm(5).to_fsd(), None);
+    }
+}
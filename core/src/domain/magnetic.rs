@@ -0,0 +1,296 @@
+//! Magnetic variation, and converting bearings between true and magnetic.
+use crate::domain::coords::{distance_nm, initial_bearing, GeoPosition};
+use crate::domain::parsing::ParseError;
+
+/// The coefficients of the World Magnetic Model 2020, in the `WMM.COF` format
+/// published by NOAA. It is meant for 2020 to 2025, and drifts slowly after that,
+/// so load a newer file with [`WorldMagneticModel::parse`] where it matters.
+const WMM2020: &str = include_str!("wmm2020.cof");
+
+/// How many years after its epoch a World Magnetic Model is meant to be used for.
+pub const MODEL_VALIDITY_YEARS: f64 = 5.;
+
+/// The reference radius of the geomagnetic models, in kilometres.
+const GEOMAGNETIC_RADIUS_KM: f64 = 6371.2;
+/// The WGS84 semi-major axis, in kilometres.
+const WGS84_A_KM: f64 = 6378.137;
+/// The WGS84 flattening.
+const WGS84_F: f64 = 1. / 298.257223563;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Coefficient {
+    n: usize,
+    m: usize,
+    g: f64,
+    h: f64,
+    g_rate: f64,
+    h_rate: f64,
+}
+
+/// A spherical harmonic model of the Earth's magnetic field, such as the WMM.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorldMagneticModel {
+    /// The decimal year the coefficients are given for.
+    pub epoch: f64,
+    degree: usize,
+    coefficients: Vec<Coefficient>,
+}
+
+impl WorldMagneticModel {
+    /// The World Magnetic Model 2020, which is built in.
+    pub fn wmm2020() -> WorldMagneticModel {
+        WorldMagneticModel::parse(WMM2020).expect("built-in magnetic model is invalid")
+    }
+
+    /// Parse a coefficient file in NOAA's `WMM.COF` format.
+    pub fn parse(src: &str) -> Result<WorldMagneticModel, ParseError> {
+        let mut lines = src.lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty());
+
+        let epoch = lines.next()
+            .and_then(|l| l.split_whitespace().next())
+            .ok_or_else(|| ParseError::new("missing header"))?;
+        let epoch = epoch.parse::<f64>()
+            .map_err(|_| ParseError::new(format!("invalid epoch: {}", epoch)))?;
+
+        let mut coefficients = Vec::new();
+        for line in lines {
+            if line.starts_with("9999") {
+                break;
+            }
+
+            let values = line.split_whitespace()
+                .map(|v| v.parse::<f64>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| ParseError::new(format!("invalid coefficients: {}", line)))?;
+            let (n, m) = match values[..] {
+                [n, m, _, _, _, _] if n >= 1. && m >= 0. && m <= n => (n as usize, m as usize),
+                _ => return Err(ParseError::new(format!("invalid coefficients: {}", line))),
+            };
+
+            coefficients.push(Coefficient {
+                n,
+                m,
+                g: values[2],
+                h: values[3],
+                g_rate: values[4],
+                h_rate: values[5],
+            });
+        }
+
+        let degree = coefficients.iter()
+            .map(|c| c.n)
+            .max()
+            .ok_or_else(|| ParseError::new("no coefficients"))?;
+        Ok(WorldMagneticModel {
+            epoch,
+            degree,
+            coefficients,
+        })
+    }
+
+    /// Whether the model is meant to be used on a decimal year. Outside of
+    /// that the coefficients are extrapolated, and drift from the real field.
+    pub fn is_valid_for(&self, year: f64) -> bool {
+        (self.epoch..self.epoch + MODEL_VALIDITY_YEARS).contains(&year)
+    }
+
+    /// The magnetic variation at a position and altitude in kilometres, at a
+    /// decimal year such as `2024.5`, in degrees east of true north.
+    pub fn declination(&self, position: GeoPosition, altitude_km: f64, year: f64) -> f64 {
+        let (north, east) = self.horizontal_field(position, altitude_km, year);
+        east.atan2(north).to_degrees()
+    }
+
+    /// The northward and eastward components of the field, in nanotesla.
+    fn horizontal_field(&self, position: GeoPosition, altitude_km: f64, year: f64) -> (f64, f64) {
+        // Convert to geocentric spherical coordinates.
+        let latitude = position.latitude.to_radians();
+        let longitude = position.longitude.to_radians();
+        let e2 = WGS84_F * (2. - WGS84_F);
+        let rc = WGS84_A_KM / (1. - e2 * latitude.sin().powi(2)).sqrt();
+        let p = (rc + altitude_km) * latitude.cos();
+        let z = (rc * (1. - e2) + altitude_km) * latitude.sin();
+        let r = p.hypot(z);
+        let geocentric_latitude = (z / r).asin();
+
+        // Schmidt semi-normalised associated Legendre functions of the
+        // colatitude, and their derivatives with respect to it.
+        let degree = self.degree;
+        let (x, s) = (geocentric_latitude.sin(), geocentric_latitude.cos());
+        let mut legendre = vec![vec![0f64; degree + 1]; degree + 1];
+        let mut derivative = vec![vec![0f64; degree + 1]; degree + 1];
+        legendre[0][0] = 1.;
+        for n in 1..=degree {
+            for m in 0..=n {
+                if n == m {
+                    let k = if n == 1 { 1. } else { ((2 * n - 1) as f64 / (2 * n) as f64).sqrt() };
+                    legendre[n][n] = k * s * legendre[n - 1][n - 1];
+                    derivative[n][n] = k * (s * derivative[n - 1][n - 1] + x * legendre[n - 1][n - 1]);
+                } else {
+                    let (nf, mf) = (n as f64, m as f64);
+                    let previous = ((nf - 1.).powi(2) - mf * mf).max(0.).sqrt();
+                    let (p2, d2) = if n >= 2 { (legendre[n - 2][m], derivative[n - 2][m]) } else { (0., 0.) };
+                    let scale = (nf * nf - mf * mf).sqrt();
+                    legendre[n][m] = ((2. * nf - 1.) * x * legendre[n - 1][m] - previous * p2) / scale;
+                    derivative[n][m] = ((2. * nf - 1.) * (x * derivative[n - 1][m] - s * legendre[n - 1][m])
+                        - previous * d2) / scale;
+                }
+            }
+        }
+
+        let dt = year - self.epoch;
+        let (mut north, mut east, mut down) = (0., 0., 0.);
+        for c in self.coefficients.iter() {
+            let g = c.g + dt * c.g_rate;
+            let h = c.h + dt * c.h_rate;
+            let ratio = (GEOMAGNETIC_RADIUS_KM / r).powi(c.n as i32 + 2);
+            let (sin_ml, cos_ml) = ((c.m as f64) * longitude).sin_cos();
+
+            north += ratio * (g * cos_ml + h * sin_ml) * derivative[c.n][c.m];
+            east += ratio * (c.m as f64) * (g * sin_ml - h * cos_ml) * legendre[c.n][c.m];
+            down -= ratio * (c.n as f64 + 1.) * (g * cos_ml + h * sin_ml) * legendre[c.n][c.m];
+        }
+        if s.abs() > 1e-10 {
+            east /= s;
+        }
+
+        // Rotate back from geocentric to geodetic north.
+        let psi = geocentric_latitude - latitude;
+        (north * psi.cos() - down * psi.sin(), east)
+    }
+}
+
+/// Where magnetic variation comes from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MagneticModel {
+    /// One value for everywhere, in degrees east, as given in sector files.
+    Fixed(f64),
+    /// A field model evaluated at sea level on a decimal year.
+    World {
+        model: WorldMagneticModel,
+        year: f64,
+    },
+}
+
+impl Default for MagneticModel {
+    fn default() -> MagneticModel {
+        MagneticModel::Fixed(0.)
+    }
+}
+
+impl MagneticModel {
+    /// The built-in World Magnetic Model on a decimal year.
+    pub fn world(year: f64) -> MagneticModel {
+        MagneticModel::World {
+            model: WorldMagneticModel::wmm2020(),
+            year,
+        }
+    }
+
+    /// The magnetic variation at a position, in degrees east of true north.
+    pub fn variation(&self, position: GeoPosition) -> f64 {
+        match self {
+            MagneticModel::Fixed(variation) => *variation,
+            MagneticModel::World { model, year } => model.declination(position, 0., *year),
+        }
+    }
+
+    /// Convert a true bearing at a position to a magnetic one, in degrees.
+    pub fn true_to_magnetic(&self, position: GeoPosition, bearing: f64) -> f64 {
+        (bearing - self.variation(position)).rem_euclid(360.)
+    }
+
+    /// Convert a magnetic bearing at a position to a true one, in degrees.
+    pub fn magnetic_to_true(&self, position: GeoPosition, bearing: f64) -> f64 {
+        (bearing + self.variation(position)).rem_euclid(360.)
+    }
+
+    /// The distance in nautical miles and magnetic bearing from one position to
+    /// another, as a range-and-bearing tool shows them.
+    pub fn range_and_bearing(&self, from: GeoPosition, to: GeoPosition) -> (f64, f64) {
+        let bearing = initial_bearing(from.into(), to.into());
+        (distance_nm(from.into(), to.into()), self.true_to_magnetic(from, bearing))
+    }
+
+    /// The radial of a VOR which a position lies on, in degrees magnetic.
+    pub fn radial(&self, station: GeoPosition, position: GeoPosition) -> f64 {
+        self.range_and_bearing(station, position).1
+    }
+}
+
+/// Convert a calendar date into a decimal year, such as `2024.5`.
+pub fn decimal_year(year: i32, month: u32, day: u32) -> f64 {
+    let is_leap = (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+    let days_before = [0, 31, 59, 90, 120, 151, 181, 212, 243, 273, 304, 334];
+    let month = month.clamp(1, 12) as usize;
+    let leap_day = if is_leap && month > 2 { 1 } else { 0 };
+    let day_of_year = days_before[month - 1] + leap_day + day.max(1) - 1;
+    let days_in_year = if is_leap { 366. } else { 365. };
+    year as f64 + day_of_year as f64 / days_in_year
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_declination() {
+        let model = WorldMagneticModel::wmm2020();
+        assert_eq!(model.epoch, 2020.);
+
+        // Approximate published variations for 2020.
+        let places = [
+            (GeoPosition::new(51.47, -0.45), 0.),
+            (GeoPosition::new(60.32, 24.96), 10.),
+            (GeoPosition::new(40.64, -73.78), -13.),
+            (GeoPosition::new(-33.95, 151.18), 12.8),
+            (GeoPosition::new(33.94, -118.41), 11.8),
+        ];
+        for (position, expected) in places {
+            let declination = model.declination(position, 0., 2020.);
+            assert!((declination - expected).abs() < 1., "{}: {} != {}", position, declination, expected);
+        }
+
+        // Variation drifts eastwards over much of Europe.
+        let helsinki = GeoPosition::new(60.32, 24.96);
+        assert!(model.declination(helsinki, 0., 2025.) > model.declination(helsinki, 0., 2020.));
+    }
+
+    #[test]
+    fn test_parse() {
+        let model = WorldMagneticModel::parse("2020.0 TEST\n1 0 -29404.5 0.0 6.7 0.0\n9999\n").unwrap();
+        assert_eq!(model.degree, 1);
+        assert!(model.is_valid_for(2020.));
+        assert!(model.is_valid_for(2024.9));
+        assert!(!model.is_valid_for(2025.));
+        assert!(!model.is_valid_for(2019.5));
+        assert!(WorldMagneticModel::parse("2020.0\n1 2 0 0 0 0\n").is_err());
+        assert!(WorldMagneticModel::parse("2020.0\n").is_err());
+        assert!(WorldMagneticModel::parse("").is_err());
+    }
+
+    #[test]
+    fn test_conversions() {
+        let position = GeoPosition::new(60., 24.);
+        let model = MagneticModel::Fixed(10.);
+        assert_eq!(model.true_to_magnetic(position, 5.), 355.);
+        assert_eq!(model.magnetic_to_true(position, 355.), 5.);
+
+        let (range, bearing) = model.range_and_bearing(position, GeoPosition::new(61., 24.));
+        assert!((range - 60.04).abs() < 0.01);
+        assert_eq!(bearing, 350.);
+        assert_eq!(model.radial(position, GeoPosition::new(59., 24.)), 170.);
+
+        let world = MagneticModel::world(2020.);
+        assert!((world.variation(position) - 9.5).abs() < 1.5);
+    }
+
+    #[test]
+    fn test_decimal_year() {
+        assert_eq!(decimal_year(2020, 1, 1), 2020.);
+        assert_eq!(decimal_year(2021, 7, 2), 2021. + 182. / 365.);
+        assert_eq!(decimal_year(2024, 12, 31), 2024. + 365. / 366.);
+    }
+}
@@ -2,8 +2,18 @@
 
 use serde::{Deserialize, Serialize};
 
+use crate::domain::frequency::Frequency;
+use crate::domain::navaid::Navaid;
+
 pub mod viewer;
 pub mod coords;
+pub mod flight_plan;
+pub mod frequency;
+pub mod magnetic;
+pub mod navaid;
+pub mod parsing;
+
+pub use flight_plan::FlightPlan;
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -18,8 +28,10 @@ pub enum FixKind {
 #[serde(rename_all = "lowercase", tag = "type")]
 pub enum PointKind {
     FIX { kind: FixKind, is_boundary: bool },
-    VOR { frequency: u16 },
-    NDB { frequency: u16 },
+    /// VHF navaids: VORs, TACANs and DMEs.
+    VOR(Navaid),
+    /// NDBs and locators.
+    NDB(Navaid),
     VRP { altitude: Option<(f32, f32)> },
 }
 
@@ -37,7 +49,7 @@ pub struct Point {
 #[serde(rename_all = "camelCase")]
 pub struct ATC {
     pub position: String,
-    pub frequency: u16,
+    pub frequency: Frequency,
     pub transfer_allow: Vec<String>,
     pub transfer_deny: Vec<String>,
 }
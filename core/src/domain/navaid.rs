@@ -0,0 +1,153 @@
+//! Radio navigation aids.
+use serde::{Deserialize, Serialize};
+
+use crate::domain::frequency::Frequency;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum NavaidKind {
+    Vor,
+    VorDme,
+    Vortac,
+    Tacan,
+    /// A DME on its own, often paired with an ILS.
+    Dme,
+    Ndb,
+    NdbDme,
+    /// A low-powered NDB used as part of an approach.
+    Locator,
+}
+
+impl NavaidKind {
+    /// Whether the navaid gives a distance.
+    pub fn has_dme(&self) -> bool {
+        matches!(self, NavaidKind::VorDme | NavaidKind::Vortac | NavaidKind::Tacan
+            | NavaidKind::Dme | NavaidKind::NdbDme)
+    }
+
+    /// Whether the navaid gives a bearing, whether as a radial or for an ADF.
+    pub fn has_bearing(&self) -> bool {
+        !matches!(self, NavaidKind::Dme)
+    }
+}
+
+/// A navaid, as much as is known about it.
+///
+/// Sector files usually only give the kind and frequency, so everything else
+/// is left for other data sources to fill in.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Navaid {
+    pub kind: NavaidKind,
+    pub frequency: Frequency,
+    /// The DME or TACAN channel, such as `91X`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dme_channel: Option<String>,
+    /// How far the navaid can be received, in nautical miles.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub range: Option<f32>,
+    /// The elevation of the station, in feet.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub elevation: Option<f32>,
+    /// The variation a VOR is aligned to, in degrees east, which may differ
+    /// from the current magnetic variation at the station.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub declination: Option<f64>,
+}
+
+impl Navaid {
+    pub fn new(kind: NavaidKind, frequency: Frequency) -> Navaid {
+        Navaid {
+            kind,
+            frequency,
+            dme_channel: None,
+            range: None,
+            elevation: None,
+            declination: None,
+        }
+    }
+}
+
+/// The DME channel paired with a VOR or ILS frequency, such as `91X` for
+/// `114.40`, or `None` if the frequency has no paired channel.
+pub fn dme_channel(frequency: Frequency) -> Option<String> {
+    let khz = match frequency {
+        Frequency::Nav(khz) if (108_000..118_000).contains(&khz) => khz,
+        _ => return None,
+    };
+
+    // In 50 kHz steps from 108 MHz.
+    let step = (khz - 108_000) / 50;
+    let suffix = if step.is_multiple_of(2) { 'X' } else { 'Y' };
+    let tenths = step / 2;
+    let channel = match tenths {
+        // 108.00 to 111.95 MHz.
+        0..=39 => 17 + tenths,
+        // 112.00 to 112.25 MHz.
+        40..=42 => 57 + (tenths - 40),
+        // 112.30 to 117.95 MHz, skipping the unpaired channels 60 to 69.
+        _ => 70 + (tenths - 43),
+    };
+    Some(format!("{}{}", channel, suffix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frequency(src: &str) -> Frequency {
+        src.parse().unwrap()
+    }
+
+    #[test]
+    fn test_dme_channel() {
+        assert_eq!(dme_channel(frequency("108.00")).as_deref(), Some("17X"));
+        assert_eq!(dme_channel(frequency("108.05")).as_deref(), Some("17Y"));
+        assert_eq!(dme_channel(frequency("110.30")).as_deref(), Some("40X"));
+        assert_eq!(dme_channel(frequency("111.95")).as_deref(), Some("56Y"));
+        assert_eq!(dme_channel(frequency("112.00")).as_deref(), Some("57X"));
+        assert_eq!(dme_channel(frequency("112.25")).as_deref(), Some("59Y"));
+        assert_eq!(dme_channel(frequency("112.30")).as_deref(), Some("70X"));
+        assert_eq!(dme_channel(frequency("114.40")).as_deref(), Some("91X"));
+        assert_eq!(dme_channel(frequency("117.95")).as_deref(), Some("126Y"));
+        assert_eq!(dme_channel(frequency("118.00")), None);
+        assert_eq!(dme_channel(frequency("375")), None);
+    }
+
+    #[test]
+    fn test_serialize() {
+        let mut navaid = Navaid::new(NavaidKind::VorDme, frequency("114.40"));
+        navaid.dme_channel = Some("91X".into());
+        let json = serde_json::to_value(&navaid).unwrap();
+        assert_eq!(json, serde_json::json!({
+            "kind": "vorDme",
+            "frequency": "114.40",
+            "dmeChannel": "91X",
+        }));
+
+        let parsed: Navaid = serde_json::from_value(serde_json::json!({
+            "kind": "locator",
+            "frequency": "338",
+        })).unwrap();
+        assert_eq!(parsed, Navaid::new(NavaidKind::Locator, frequency("338")));
+        assert!(!parsed.kind.has_dme());
+        assert!(NavaidKind::Vortac.has_dme());
+        assert!(!NavaidKind::Dme.has_bearing());
+    }
+
+    #[test]
+    fn test_point() {
+        let point = crate::domain::Point {
+            kind: crate::domain::PointKind::VOR(Navaid::new(NavaidKind::Vor, frequency("114.40"))),
+            name: "ABC".into(),
+            map_position: (0.5, 0.5),
+        };
+        assert_eq!(serde_json::to_value(&point).unwrap(), serde_json::json!({
+            "type": "vor",
+            "kind": "vor",
+            "frequency": "114.40",
+            "name": "ABC",
+            "mapPosition": [0.5, 0.5],
+        }));
+    }
+}
@@ -4,7 +4,7 @@ use std::collections::HashMap;
 use serde::{Deserialize, Serialize};
 
 use crate::domain::{Airport, Airspace, Airway, Point, Runway};
-use crate::domain::coords::calculate_aabb;
+use crate::domain::coords::{calculate_aabb, Projection};
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Palette(pub HashMap<String, u32>);
@@ -82,6 +82,13 @@ impl Shape {
 #[serde(rename_all = "camelCase")]
 pub struct Global {
     pub palette: Palette,
+    /// The projection used for every map position, so they can be turned back into positions.
+    #[serde(default)]
+    pub projection: Projection,
+    /// The magnetic variation at the centre of the map, in degrees east, for
+    /// showing magnetic bearings.
+    #[serde(default)]
+    pub magnetic_variation: f64,
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -108,8 +115,15 @@ pub struct SectionBuilder {
 
 impl SectionBuilder {
     pub fn new(levels: i16) -> SectionBuilder {
+        SectionBuilder::with_projection(levels, Projection::Mercator)
+    }
+
+    pub fn with_projection(levels: i16, projection: Projection) -> SectionBuilder {
         SectionBuilder {
-            global: Global::default(),
+            global: Global {
+                projection,
+                ..Default::default()
+            },
             levels,
             sections: HashMap::new(),
         }
@@ -119,6 +133,10 @@ impl SectionBuilder {
         self.levels
     }
 
+    pub fn projection(&self) -> Projection {
+        self.global.projection
+    }
+
     pub fn truncate_f64(&self, level: i16, v: f64) -> f64 {
         if level < self.levels() - 1 {
             let scale = (1 << ((level as i64) + 9)) as f64;
@@ -147,11 +165,11 @@ impl SectionBuilder {
     }
 
     fn create_section(&mut self, level: i16, x: i16, y: i16) -> &mut Section {
-        if !self.sections.contains_key(&(level, x, y)) {
+        self.sections.entry((level, x, y)).or_insert_with(|| {
             let divisions = 1 << level;
             let scale = 1. / (divisions as f64);
 
-            self.sections.insert((level, x, y), Section {
+            Section {
                 division: (level, x, y),
                 map_bounds: (
                     (x as f64) * scale,
@@ -160,18 +178,18 @@ impl SectionBuilder {
                     ((y + 1) as f64) * scale,
                 ),
                 ..Default::default()
-            });
-        }
-
-        self.sections.get_mut(&(level, x, y)).unwrap()
+            }
+        })
     }
 
     pub fn apply_by_bounds(&mut self, level: i16, aabb: (f64, f64, f64, f64), mut f: impl FnMut(&mut Section)) {
         let divisions = (1 << level) as f64;
+        // Bounds which lie exactly on a division, such as a point at the
+        // centre of a centred projection, still belong to one section.
         let x_min = (aabb.0.min(aabb.2) * divisions).floor() as i16;
-        let x_max = (aabb.0.max(aabb.2) * divisions).ceil() as i16;
+        let x_max = ((aabb.0.max(aabb.2) * divisions).ceil() as i16).max(x_min + 1);
         let y_min = (aabb.1.min(aabb.3) * divisions).floor() as i16;
-        let y_max = (aabb.1.max(aabb.3) * divisions).ceil() as i16;
+        let y_max = ((aabb.1.max(aabb.3) * divisions).ceil() as i16).max(y_min + 1);
 
         for x in x_min..x_max {
             for y in y_min..y_max {
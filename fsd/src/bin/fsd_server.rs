@@ -0,0 +1,33 @@
+use clap::Clap;
+use fsd::server::{Server, ServerConfig};
+
+#[derive(Clap)]
+struct Opts {
+    #[clap(short, long, default_value = "0.0.0.0:6809")]
+    bind: String,
+
+    #[clap(long)]
+    name: Option<String>,
+
+    /// The visibility range of pilots, in nautical miles.
+    #[clap(long)]
+    pilot_range: Option<f64>,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    env_logger::init();
+
+    let opts = Opts::parse();
+    let mut config = ServerConfig::default();
+    if let Some(name) = opts.name {
+        config.name = name;
+    }
+    if let Some(range) = opts.pilot_range {
+        config.pilot_range = range;
+    }
+
+    let server = Server::bind(&opts.bind, config).await?;
+    log::info!("listening on {}", server.local_addr()?);
+    server.run().await
+}
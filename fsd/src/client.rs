@@ -0,0 +1,337 @@
+//! An asynchronous client for connecting to FSD servers.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use anyhow::anyhow;
+use log::{debug, warn};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpStream, ToSocketAddrs};
+use tokio::net::tcp::OwnedWriteHalf;
+use tokio::sync::{mpsc, watch};
+use tokio_stream::Stream;
+
+use crate::packet::{self, AddAtc, AddPilot, ClientIdentification, Delete, Packet, SERVER};
+
+/// The type of client to log in as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientKind {
+    Pilot { sim_type: u8 },
+    Atc,
+}
+
+/// The details used to identify and log in to the server.
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    pub kind: ClientKind,
+    pub callsign: String,
+    pub real_name: String,
+    pub cid: String,
+    pub password: String,
+    pub rating: u8,
+    pub protocol: u8,
+
+    pub client_id: String,
+    pub client_name: String,
+    pub major_version: u32,
+    pub minor_version: u32,
+    pub system_id: String,
+}
+
+impl ClientConfig {
+    /// Create a new configuration with the default client identification.
+    pub fn new(kind: ClientKind, callsign: impl Into<String>, cid: impl Into<String>, password: impl Into<String>) -> ClientConfig {
+        ClientConfig {
+            kind,
+            callsign: callsign.into(),
+            real_name: String::new(),
+            cid: cid.into(),
+            password: password.into(),
+            rating: 1,
+            protocol: 9,
+
+            client_id: "0000".into(),
+            client_name: env!("CARGO_PKG_NAME").into(),
+            major_version: env!("CARGO_PKG_VERSION_MAJOR").parse().unwrap_or(0),
+            minor_version: env!("CARGO_PKG_VERSION_MINOR").parse().unwrap_or(0),
+            system_id: "0".into(),
+        }
+    }
+
+    fn identification(&self, challenge: &str) -> Packet {
+        Packet::ClientIdentification(ClientIdentification {
+            from: self.callsign.clone(),
+            to: SERVER.into(),
+            client_id: self.client_id.clone(),
+            client_name: self.client_name.clone(),
+            major_version: self.major_version,
+            minor_version: self.minor_version,
+            cid: self.cid.clone(),
+            system_id: self.system_id.clone(),
+            challenge: challenge.into(),
+        })
+    }
+
+    fn add_client(&self) -> Packet {
+        match self.kind {
+            ClientKind::Pilot { sim_type } => Packet::AddPilot(AddPilot {
+                callsign: self.callsign.clone(),
+                to: SERVER.into(),
+                cid: self.cid.clone(),
+                password: self.password.clone(),
+                rating: self.rating,
+                protocol: self.protocol,
+                sim_type,
+                real_name: self.real_name.clone(),
+            }),
+            ClientKind::Atc => Packet::AddAtc(AddAtc {
+                callsign: self.callsign.clone(),
+                to: SERVER.into(),
+                real_name: self.real_name.clone(),
+                cid: self.cid.clone(),
+                password: self.password.clone(),
+                rating: self.rating,
+                protocol: Some(self.protocol),
+            }),
+        }
+    }
+
+    fn delete_client(&self) -> Packet {
+        let delete = Delete {
+            callsign: self.callsign.clone(),
+            cid: Some(self.cid.clone()),
+        };
+
+        match self.kind {
+            ClientKind::Pilot { .. } => Packet::DeletePilot(delete),
+            ClientKind::Atc => Packet::DeleteAtc(delete),
+        }
+    }
+}
+
+/// The state of the connection to the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// Connected, waiting for the server to identify itself.
+    Connecting,
+    /// The login packets have been sent.
+    LoggedIn,
+    /// The server removed us from the network, with the given reason.
+    Kicked(String),
+    /// The connection was closed.
+    Disconnected,
+}
+
+/// Something which happened on the connection.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    State(ConnectionState),
+    Packet(Packet),
+    Error(packet::Error),
+}
+
+/// A connection to an FSD server.
+///
+/// Incoming packets and state changes are delivered as [`Event`]s, by polling
+/// the client as a `Stream`.
+pub struct Client {
+    config: ClientConfig,
+    outgoing: mpsc::UnboundedSender<Packet>,
+    events: mpsc::UnboundedReceiver<Event>,
+    state: watch::Receiver<ConnectionState>,
+}
+
+impl Client {
+    /// Connect to a server and start the login handshake.
+    pub async fn connect(addr: impl ToSocketAddrs, config: ClientConfig) -> anyhow::Result<Client> {
+        let stream = TcpStream::connect(addr).await?;
+        let (outgoing, outgoing_rx) = mpsc::unbounded_channel();
+        let (events_tx, events) = mpsc::unbounded_channel();
+        let (state_tx, state) = watch::channel(ConnectionState::Connecting);
+
+        let connection = Connection {
+            config: config.clone(),
+            events: events_tx,
+            state: state_tx,
+        };
+        let _ = connection.events.send(Event::State(ConnectionState::Connecting));
+        tokio::spawn(connection.run(stream, outgoing_rx));
+
+        Ok(Client {
+            config,
+            outgoing,
+            events,
+            state,
+        })
+    }
+
+    /// Get the current connection state.
+    pub fn state(&self) -> ConnectionState {
+        self.state.borrow().clone()
+    }
+
+    /// Queue a packet to be sent to the server.
+    ///
+    /// Packets queued before the handshake completes are sent after logging in.
+    pub fn send(&self, packet: Packet) -> anyhow::Result<()> {
+        self.outgoing.send(packet)
+            .map_err(|_| anyhow!("connection closed"))
+    }
+
+    /// Wait for the next event on this connection.
+    ///
+    /// Returns `None` once the connection has closed and all events have been
+    /// consumed.
+    pub async fn next_event(&mut self) -> Option<Event> {
+        self.events.recv().await
+    }
+
+    /// Log out from the server and close the connection.
+    pub fn disconnect(self) {
+        if self.state() == ConnectionState::LoggedIn {
+            let _ = self.send(self.config.delete_client());
+        }
+    }
+}
+
+impl Stream for Client {
+    type Item = Event;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Event>> {
+        self.events.poll_recv(cx)
+    }
+}
+
+struct Connection {
+    config: ClientConfig,
+    events: mpsc::UnboundedSender<Event>,
+    state: watch::Sender<ConnectionState>,
+}
+
+impl Connection {
+    fn set_state(&self, state: ConnectionState) {
+        self.state.send_replace(state.clone());
+        let _ = self.events.send(Event::State(state));
+    }
+
+    async fn write(writer: &mut OwnedWriteHalf, packet: &Packet) -> anyhow::Result<()> {
+        debug!("> {}", packet);
+        writer.write_all(format!("{}\r\n", packet).as_bytes()).await?;
+        Ok(())
+    }
+
+    async fn run(self, stream: TcpStream, outgoing: mpsc::UnboundedReceiver<Packet>) {
+        let state = match self.process(stream, outgoing).await {
+            Ok(state) => state,
+            Err(err) => {
+                warn!("FSD connection failed: {}", err);
+                ConnectionState::Disconnected
+            }
+        };
+        self.set_state(state);
+    }
+
+    async fn process(&self, stream: TcpStream, mut outgoing: mpsc::UnboundedReceiver<Packet>) -> anyhow::Result<ConnectionState> {
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        let mut logged_in = false;
+
+        loop {
+            tokio::select! {
+                line = lines.next_line() => {
+                    let line = match line? {
+                        Some(line) => line,
+                        None => return Ok(ConnectionState::Disconnected),
+                    };
+                    debug!("< {}", line);
+
+                    let packet = match Packet::parse(&line) {
+                        Ok(packet) => packet,
+                        Err(err) => {
+                            warn!("ignoring malformed packet {:?}: {}", line, err);
+                            continue;
+                        }
+                    };
+
+                    match packet {
+                        Packet::ServerIdentification(ident) if !logged_in => {
+                            Self::write(&mut writer, &self.config.identification(&ident.challenge)).await?;
+                            Self::write(&mut writer, &self.config.add_client()).await?;
+                            logged_in = true;
+                            self.set_state(ConnectionState::LoggedIn);
+                        }
+                        Packet::Kill(kill) => return Ok(ConnectionState::Kicked(kill.reason)),
+                        Packet::Error(err) => {
+                            let _ = self.events.send(Event::Error(err));
+                        }
+                        packet => {
+                            let _ = self.events.send(Event::Packet(packet));
+                        }
+                    }
+                }
+                packet = outgoing.recv(), if logged_in => {
+                    match packet {
+                        Some(packet) => Self::write(&mut writer, &packet).await?,
+                        None => return Ok(ConnectionState::Disconnected),
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+    use tokio_stream::StreamExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_login() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+
+            writer.write_all(b"$DISERVER:CLIENT:test:abcdef\r\n").await.unwrap();
+
+            let ident = Packet::parse(&lines.next_line().await.unwrap().unwrap()).unwrap();
+            match ident {
+                Packet::ClientIdentification(ident) => assert_eq!(ident.challenge, "abcdef"),
+                other => panic!("expected client identification, got {:?}", other),
+            }
+
+            let add = Packet::parse(&lines.next_line().await.unwrap().unwrap()).unwrap();
+            match add {
+                Packet::AddPilot(add) => assert_eq!(add.callsign, "BAW123"),
+                other => panic!("expected add pilot, got {:?}", other),
+            }
+
+            let message = lines.next_line().await.unwrap().unwrap();
+            assert_eq!(message, "#TMBAW123:EGLL_TWR:hello");
+
+            writer.write_all(b"$ERserver:BAW123:008:BAW123:No flightplan\r\n").await.unwrap();
+            writer.write_all(b"$!!SERVER:BAW123:bye\r\n").await.unwrap();
+        });
+
+        let config = ClientConfig::new(ClientKind::Pilot { sim_type: 1 }, "BAW123", "123456", "secret");
+        let mut client = Client::connect(addr, config).await.unwrap();
+        client.send(Packet::parse("#TMBAW123:EGLL_TWR:hello").unwrap()).unwrap();
+
+        assert_eq!(client.next().await, Some(Event::State(ConnectionState::Connecting)));
+        assert_eq!(client.next().await, Some(Event::State(ConnectionState::LoggedIn)));
+        match client.next().await {
+            Some(Event::Error(err)) => assert_eq!(err.code_number(), Some(8)),
+            other => panic!("expected error, got {:?}", other),
+        }
+        assert_eq!(client.next().await, Some(Event::State(ConnectionState::Kicked("bye".into()))));
+        assert_eq!(client.next().await, None);
+        assert_eq!(client.state(), ConnectionState::Kicked("bye".into()));
+
+        server.await.unwrap();
+    }
+}
@@ -1,8 +1,5 @@
-#[cfg(test)]
-mod tests {
-    #[test]
-    fn it_works() {
-        let result = 2 + 2;
-        assert_eq!(result, 4);
-    }
-}
+//! A library for interacting with FSD servers and clients.
+
+pub mod packet;
+pub mod client;
+pub mod server;
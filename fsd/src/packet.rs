@@ -0,0 +1,720 @@
+//! Typed representations of the packets in the FSD text protocol.
+//!
+//! Every packet is a single line consisting of a short prefix, which identifies
+//! the packet type, followed by colon-separated fields. Packets which aren't
+//! understood are preserved verbatim so that they can be relayed untouched.
+
+use std::fmt;
+use std::str::{FromStr, Split};
+
+use anyhow::anyhow;
+
+/// The server pseudo-callsign used as the destination of login packets.
+pub const SERVER: &str = "SERVER";
+
+fn next_field<'a>(parts: &mut Split<'a, char>, name: &str) -> anyhow::Result<&'a str> {
+    parts.next().ok_or_else(|| anyhow!("missing {}", name))
+}
+
+fn next_string(parts: &mut Split<'_, char>, name: &str) -> anyhow::Result<String> {
+    Ok(next_field(parts, name)?.to_owned())
+}
+
+fn next_parsed<T>(parts: &mut Split<'_, char>, name: &str) -> anyhow::Result<T>
+    where T: FromStr, T::Err: std::error::Error + Send + Sync + 'static {
+    let field = next_field(parts, name)?;
+    field.parse::<T>()
+        .map_err(|e| anyhow!("invalid {} {:?}: {}", name, field, e))
+}
+
+/// Parse a trailing field which older clients leave out.
+fn next_optional<T>(parts: &mut Split<'_, char>, name: &str) -> anyhow::Result<Option<T>>
+    where T: FromStr, T::Err: std::error::Error + Send + Sync + 'static {
+    parts.next()
+        .filter(|field| !field.is_empty())
+        .map(|field| field.parse::<T>()
+            .map_err(|e| anyhow!("invalid {} {:?}: {}", name, field, e)))
+        .transpose()
+}
+
+/// Write a trailing field which may have been left out.
+fn write_optional<T: fmt::Display>(f: &mut fmt::Formatter<'_>, field: &Option<T>) -> fmt::Result {
+    match field {
+        Some(value) => write!(f, ":{}", value),
+        None => Ok(()),
+    }
+}
+
+/// Collect the remaining fields, re-joining them with the separator.
+///
+/// This is used for trailing free-text fields which may themselves contain colons.
+fn rest(parts: Split<'_, char>) -> String {
+    parts.collect::<Vec<_>>().join(":")
+}
+
+fn rest_vec(parts: Split<'_, char>) -> Vec<String> {
+    parts.map(String::from).collect()
+}
+
+/// Sent by an ATC client to log in to the server.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AddAtc {
+    pub callsign: String,
+    pub to: String,
+    pub real_name: String,
+    pub cid: String,
+    pub password: String,
+    pub rating: u8,
+    /// Left out by older clients.
+    pub protocol: Option<u8>,
+}
+
+impl AddAtc {
+    fn parse(mut parts: Split<'_, char>) -> anyhow::Result<AddAtc> {
+        Ok(AddAtc {
+            callsign: next_string(&mut parts, "callsign")?,
+            to: next_string(&mut parts, "destination")?,
+            real_name: next_string(&mut parts, "real name")?,
+            cid: next_string(&mut parts, "cid")?,
+            password: next_string(&mut parts, "password")?,
+            rating: next_parsed(&mut parts, "rating")?,
+            protocol: next_optional(&mut parts, "protocol")?,
+        })
+    }
+}
+
+impl fmt::Display for AddAtc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#AA{}:{}:{}:{}:{}:{}", self.callsign, self.to, self.real_name,
+               self.cid, self.password, self.rating)?;
+        write_optional(f, &self.protocol)
+    }
+}
+
+/// Sent by a pilot client to log in to the server.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AddPilot {
+    pub callsign: String,
+    pub to: String,
+    pub cid: String,
+    pub password: String,
+    pub rating: u8,
+    pub protocol: u8,
+    pub sim_type: u8,
+    pub real_name: String,
+}
+
+impl AddPilot {
+    fn parse(mut parts: Split<'_, char>) -> anyhow::Result<AddPilot> {
+        Ok(AddPilot {
+            callsign: next_string(&mut parts, "callsign")?,
+            to: next_string(&mut parts, "destination")?,
+            cid: next_string(&mut parts, "cid")?,
+            password: next_string(&mut parts, "password")?,
+            rating: next_parsed(&mut parts, "rating")?,
+            protocol: next_parsed(&mut parts, "protocol")?,
+            sim_type: next_parsed(&mut parts, "simulator type")?,
+            real_name: rest(parts),
+        })
+    }
+}
+
+impl fmt::Display for AddPilot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#AP{}:{}:{}:{}:{}:{}:{}:{}", self.callsign, self.to, self.cid,
+               self.password, self.rating, self.protocol, self.sim_type, self.real_name)
+    }
+}
+
+/// Sent when a client leaves the network, either voluntarily or by the server.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Delete {
+    pub callsign: String,
+    /// Left out when the server removes a client.
+    pub cid: Option<String>,
+}
+
+impl Delete {
+    fn parse(mut parts: Split<'_, char>) -> anyhow::Result<Delete> {
+        Ok(Delete {
+            callsign: next_string(&mut parts, "callsign")?,
+            cid: parts.next().filter(|cid| !cid.is_empty()).map(str::to_owned),
+        })
+    }
+
+    fn write(&self, f: &mut fmt::Formatter<'_>, prefix: &str) -> fmt::Result {
+        write!(f, "{}{}", prefix, self.callsign)?;
+        if let Some(cid) = &self.cid {
+            write!(f, ":{}", cid)?;
+        }
+        Ok(())
+    }
+}
+
+/// The transponder mode reported in pilot position updates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransponderMode {
+    Standby,
+    Normal,
+    Ident,
+}
+
+impl TransponderMode {
+    fn as_str(&self) -> &'static str {
+        match self {
+            TransponderMode::Standby => "S",
+            TransponderMode::Normal => "N",
+            TransponderMode::Ident => "Y",
+        }
+    }
+}
+
+impl FromStr for TransponderMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<TransponderMode> {
+        Ok(match s {
+            "S" => TransponderMode::Standby,
+            "N" => TransponderMode::Normal,
+            "Y" => TransponderMode::Ident,
+            _ => return Err(anyhow!("invalid transponder mode {}", s)),
+        })
+    }
+}
+
+/// A periodic position update from a pilot.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PilotPosition {
+    pub mode: TransponderMode,
+    pub callsign: String,
+    pub squawk: String,
+    pub rating: u8,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub altitude: i32,
+    pub ground_speed: u32,
+    /// Pitch, bank & heading packed into a single integer.
+    pub pbh: u32,
+    /// Left out by older clients.
+    pub pressure_delta: Option<i32>,
+}
+
+impl PilotPosition {
+    fn parse(mut parts: Split<'_, char>) -> anyhow::Result<PilotPosition> {
+        Ok(PilotPosition {
+            mode: next_field(&mut parts, "transponder mode")?.parse()?,
+            callsign: next_string(&mut parts, "callsign")?,
+            squawk: next_string(&mut parts, "squawk")?,
+            rating: next_parsed(&mut parts, "rating")?,
+            latitude: next_parsed(&mut parts, "latitude")?,
+            longitude: next_parsed(&mut parts, "longitude")?,
+            altitude: next_parsed(&mut parts, "altitude")?,
+            ground_speed: next_parsed(&mut parts, "ground speed")?,
+            pbh: next_parsed(&mut parts, "pitch/bank/heading")?,
+            pressure_delta: next_optional(&mut parts, "pressure delta")?,
+        })
+    }
+
+    fn unpack_angle(&self, shift: u32) -> f64 {
+        let raw = (self.pbh >> shift) & 0x3ff;
+        let degrees = (raw as f64) * 360. / 1024.;
+        if degrees > 180. { degrees - 360. } else { degrees }
+    }
+
+    /// The pitch of the aircraft in degrees, positive is nose down.
+    pub fn pitch(&self) -> f64 {
+        self.unpack_angle(22)
+    }
+
+    /// The bank of the aircraft in degrees, positive is right wing down.
+    pub fn bank(&self) -> f64 {
+        self.unpack_angle(12)
+    }
+
+    /// The true heading of the aircraft in degrees.
+    pub fn heading(&self) -> f64 {
+        (((self.pbh >> 2) & 0x3ff) as f64) * 360. / 1024.
+    }
+
+    /// Whether the aircraft reports being on the ground.
+    pub fn on_ground(&self) -> bool {
+        (self.pbh & 2) != 0
+    }
+}
+
+impl fmt::Display for PilotPosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "@{}:{}:{}:{}:{}:{}:{}:{}:{}", self.mode.as_str(), self.callsign,
+               self.squawk, self.rating, self.latitude, self.longitude, self.altitude,
+               self.ground_speed, self.pbh)?;
+        write_optional(f, &self.pressure_delta)
+    }
+}
+
+/// A periodic position update from an ATC client.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AtcPosition {
+    pub callsign: String,
+    /// The primary frequency, in kHz above 100MHz.
+    pub frequency: u32,
+    pub facility: u8,
+    /// The visibility range in nautical miles.
+    pub visual_range: u32,
+    pub rating: u8,
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Left out by older clients.
+    pub altitude: Option<i32>,
+}
+
+impl AtcPosition {
+    fn parse(mut parts: Split<'_, char>) -> anyhow::Result<AtcPosition> {
+        Ok(AtcPosition {
+            callsign: next_string(&mut parts, "callsign")?,
+            frequency: next_parsed(&mut parts, "frequency")?,
+            facility: next_parsed(&mut parts, "facility")?,
+            visual_range: next_parsed(&mut parts, "visual range")?,
+            rating: next_parsed(&mut parts, "rating")?,
+            latitude: next_parsed(&mut parts, "latitude")?,
+            longitude: next_parsed(&mut parts, "longitude")?,
+            altitude: next_optional(&mut parts, "altitude")?,
+        })
+    }
+}
+
+impl fmt::Display for AtcPosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "%{}:{}:{}:{}:{}:{}:{}", self.callsign, self.frequency, self.facility,
+               self.visual_range, self.rating, self.latitude, self.longitude)?;
+        write_optional(f, &self.altitude)
+    }
+}
+
+/// A text message, sent either to a callsign, a frequency or a broadcast group.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextMessage {
+    pub from: String,
+    pub to: String,
+    pub message: String,
+}
+
+impl TextMessage {
+    fn parse(mut parts: Split<'_, char>) -> anyhow::Result<TextMessage> {
+        Ok(TextMessage {
+            from: next_string(&mut parts, "sender")?,
+            to: next_string(&mut parts, "recipient")?,
+            message: rest(parts),
+        })
+    }
+
+    /// Whether this message is sent to everyone connected.
+    pub fn is_broadcast(&self) -> bool {
+        self.to == "*"
+    }
+
+    /// Iterate the frequencies (in kHz above 100MHz) this message was sent on.
+    ///
+    /// Frequency messages are addressed as `@18700`, multiple frequencies are
+    /// joined with `&`.
+    pub fn frequencies(&self) -> impl Iterator<Item=u32> + '_ {
+        self.to.split('&')
+            .filter_map(|part| part.strip_prefix('@'))
+            .filter_map(|freq| freq.parse().ok())
+    }
+}
+
+impl fmt::Display for TextMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#TM{}:{}:{}", self.from, self.to, self.message)
+    }
+}
+
+/// A query sent to another client, or the response to one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientQuery {
+    pub from: String,
+    pub to: String,
+    pub query: String,
+    pub args: Vec<String>,
+}
+
+impl ClientQuery {
+    fn parse(mut parts: Split<'_, char>) -> anyhow::Result<ClientQuery> {
+        Ok(ClientQuery {
+            from: next_string(&mut parts, "sender")?,
+            to: next_string(&mut parts, "recipient")?,
+            query: next_string(&mut parts, "query type")?,
+            args: rest_vec(parts),
+        })
+    }
+
+    fn write(&self, f: &mut fmt::Formatter<'_>, prefix: &str) -> fmt::Result {
+        write!(f, "{}{}:{}:{}", prefix, self.from, self.to, self.query)?;
+        for arg in self.args.iter() {
+            write!(f, ":{}", arg)?;
+        }
+        Ok(())
+    }
+}
+
+/// A flight plan filed by a pilot.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlightPlan {
+    pub callsign: String,
+    pub to: String,
+    pub rules: String,
+    pub aircraft: String,
+    pub cruise_speed: String,
+    pub departure: String,
+    pub departure_time: String,
+    pub actual_departure_time: String,
+    pub cruise_altitude: String,
+    pub destination: String,
+    pub hours_enroute: String,
+    pub minutes_enroute: String,
+    pub hours_fuel: String,
+    pub minutes_fuel: String,
+    pub alternate: String,
+    pub remarks: String,
+    pub route: String,
+}
+
+impl FlightPlan {
+    fn parse(mut parts: Split<'_, char>) -> anyhow::Result<FlightPlan> {
+        Ok(FlightPlan {
+            callsign: next_string(&mut parts, "callsign")?,
+            to: next_string(&mut parts, "recipient")?,
+            rules: next_string(&mut parts, "flight rules")?,
+            aircraft: next_string(&mut parts, "aircraft")?,
+            cruise_speed: next_string(&mut parts, "cruise speed")?,
+            departure: next_string(&mut parts, "departure")?,
+            departure_time: next_string(&mut parts, "departure time")?,
+            actual_departure_time: next_string(&mut parts, "actual departure time")?,
+            cruise_altitude: next_string(&mut parts, "cruise altitude")?,
+            destination: next_string(&mut parts, "destination")?,
+            hours_enroute: next_string(&mut parts, "hours enroute")?,
+            minutes_enroute: next_string(&mut parts, "minutes enroute")?,
+            hours_fuel: next_string(&mut parts, "hours of fuel")?,
+            minutes_fuel: next_string(&mut parts, "minutes of fuel")?,
+            alternate: next_string(&mut parts, "alternate")?,
+            remarks: next_string(&mut parts, "remarks")?,
+            route: rest(parts),
+        })
+    }
+}
+
+impl fmt::Display for FlightPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "$FP{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}",
+               self.callsign, self.to, self.rules, self.aircraft, self.cruise_speed,
+               self.departure, self.departure_time, self.actual_departure_time,
+               self.cruise_altitude, self.destination, self.hours_enroute,
+               self.minutes_enroute, self.hours_fuel, self.minutes_fuel, self.alternate,
+               self.remarks, self.route)
+    }
+}
+
+/// An error reported by the server.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Error {
+    pub from: String,
+    pub to: String,
+    /// The code as it was sent, usually three digits such as `006`.
+    pub code: String,
+    pub parameter: String,
+    pub message: String,
+}
+
+impl Error {
+    fn parse(mut parts: Split<'_, char>) -> anyhow::Result<Error> {
+        Ok(Error {
+            from: next_string(&mut parts, "sender")?,
+            to: next_string(&mut parts, "recipient")?,
+            code: next_string(&mut parts, "error code")?,
+            parameter: next_string(&mut parts, "error parameter")?,
+            message: rest(parts),
+        })
+    }
+
+    /// The numeric value of the code, if it is a number.
+    pub fn code_number(&self) -> Option<u32> {
+        self.code.parse().ok()
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "$ER{}:{}:{}:{}:{}", self.from, self.to, self.code, self.parameter, self.message)
+    }
+}
+
+/// Sent by the server immediately after a client connects.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerIdentification {
+    pub from: String,
+    pub to: String,
+    pub version: String,
+    pub challenge: String,
+}
+
+impl ServerIdentification {
+    fn parse(mut parts: Split<'_, char>) -> anyhow::Result<ServerIdentification> {
+        Ok(ServerIdentification {
+            from: next_string(&mut parts, "sender")?,
+            to: next_string(&mut parts, "recipient")?,
+            version: next_string(&mut parts, "server version")?,
+            challenge: rest(parts),
+        })
+    }
+}
+
+impl fmt::Display for ServerIdentification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "$DI{}:{}:{}:{}", self.from, self.to, self.version, self.challenge)
+    }
+}
+
+/// Sent by a client in response to the server identification.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientIdentification {
+    pub from: String,
+    pub to: String,
+    pub client_id: String,
+    pub client_name: String,
+    pub major_version: u32,
+    pub minor_version: u32,
+    pub cid: String,
+    pub system_id: String,
+    pub challenge: String,
+}
+
+impl ClientIdentification {
+    fn parse(mut parts: Split<'_, char>) -> anyhow::Result<ClientIdentification> {
+        Ok(ClientIdentification {
+            from: next_string(&mut parts, "sender")?,
+            to: next_string(&mut parts, "recipient")?,
+            client_id: next_string(&mut parts, "client id")?,
+            client_name: next_string(&mut parts, "client name")?,
+            major_version: next_parsed(&mut parts, "major version")?,
+            minor_version: next_parsed(&mut parts, "minor version")?,
+            cid: next_string(&mut parts, "cid")?,
+            system_id: next_string(&mut parts, "system id")?,
+            challenge: rest(parts),
+        })
+    }
+}
+
+impl fmt::Display for ClientIdentification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "$ID{}:{}:{}:{}:{}:{}:{}:{}:{}", self.from, self.to, self.client_id,
+               self.client_name, self.major_version, self.minor_version, self.cid,
+               self.system_id, self.challenge)
+    }
+}
+
+/// Sent by the server (or a supervisor) to remove a client from the network.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Kill {
+    pub from: String,
+    pub to: String,
+    pub reason: String,
+}
+
+impl Kill {
+    fn parse(mut parts: Split<'_, char>) -> anyhow::Result<Kill> {
+        Ok(Kill {
+            from: next_string(&mut parts, "sender")?,
+            to: next_string(&mut parts, "recipient")?,
+            reason: rest(parts),
+        })
+    }
+}
+
+impl fmt::Display for Kill {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "$!!{}:{}:{}", self.from, self.to, self.reason)
+    }
+}
+
+/// A single FSD protocol packet.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Packet {
+    AddAtc(AddAtc),
+    AddPilot(AddPilot),
+    DeleteAtc(Delete),
+    DeletePilot(Delete),
+    PilotPosition(PilotPosition),
+    AtcPosition(AtcPosition),
+    TextMessage(TextMessage),
+    ClientQuery(ClientQuery),
+    ClientResponse(ClientQuery),
+    FlightPlan(Box<FlightPlan>),
+    Error(Error),
+    ServerIdentification(ServerIdentification),
+    ClientIdentification(ClientIdentification),
+    Kill(Kill),
+    /// A packet which isn't understood, stored as the raw line.
+    Unknown(String),
+}
+
+impl Packet {
+    /// Parse a single line (without the line terminator) into a packet.
+    ///
+    /// Lines with unrecognised prefixes are returned as `Packet::Unknown`. An
+    /// error is only returned when a recognised packet is malformed.
+    pub fn parse(line: &str) -> anyhow::Result<Packet> {
+        let line = line.trim_end_matches(['\r', '\n']);
+
+        let packet = if let Some(body) = line.strip_prefix('@') {
+            Packet::PilotPosition(PilotPosition::parse(body.split(':'))?)
+        } else if let Some(body) = line.strip_prefix('%') {
+            Packet::AtcPosition(AtcPosition::parse(body.split(':'))?)
+        } else if line.len() >= 3 && line.is_char_boundary(3) {
+            let parts = line[3..].split(':');
+            match &line[..3] {
+                "#AA" => Packet::AddAtc(AddAtc::parse(parts)?),
+                "#AP" => Packet::AddPilot(AddPilot::parse(parts)?),
+                "#DA" => Packet::DeleteAtc(Delete::parse(parts)?),
+                "#DP" => Packet::DeletePilot(Delete::parse(parts)?),
+                "#TM" => Packet::TextMessage(TextMessage::parse(parts)?),
+                "$CQ" => Packet::ClientQuery(ClientQuery::parse(parts)?),
+                "$CR" => Packet::ClientResponse(ClientQuery::parse(parts)?),
+                "$FP" => Packet::FlightPlan(Box::new(FlightPlan::parse(parts)?)),
+                "$ER" => Packet::Error(Error::parse(parts)?),
+                "$DI" => Packet::ServerIdentification(ServerIdentification::parse(parts)?),
+                "$ID" => Packet::ClientIdentification(ClientIdentification::parse(parts)?),
+                "$!!" => Packet::Kill(Kill::parse(parts)?),
+                _ => Packet::Unknown(line.to_owned()),
+            }
+        } else {
+            Packet::Unknown(line.to_owned())
+        };
+        Ok(packet)
+    }
+
+    /// Get the callsign of the client which sent this packet, if known.
+    pub fn sender(&self) -> Option<&str> {
+        Some(match self {
+            Packet::AddAtc(p) => &p.callsign,
+            Packet::AddPilot(p) => &p.callsign,
+            Packet::DeleteAtc(p) | Packet::DeletePilot(p) => &p.callsign,
+            Packet::PilotPosition(p) => &p.callsign,
+            Packet::AtcPosition(p) => &p.callsign,
+            Packet::TextMessage(p) => &p.from,
+            Packet::ClientQuery(p) | Packet::ClientResponse(p) => &p.from,
+            Packet::FlightPlan(p) => &p.callsign,
+            Packet::Error(p) => &p.from,
+            Packet::ServerIdentification(p) => &p.from,
+            Packet::ClientIdentification(p) => &p.from,
+            Packet::Kill(p) => &p.from,
+            Packet::Unknown(_) => return None,
+        })
+    }
+}
+
+impl FromStr for Packet {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Packet> {
+        Packet::parse(s)
+    }
+}
+
+impl fmt::Display for Packet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Packet::AddAtc(p) => p.fmt(f),
+            Packet::AddPilot(p) => p.fmt(f),
+            Packet::DeleteAtc(p) => p.write(f, "#DA"),
+            Packet::DeletePilot(p) => p.write(f, "#DP"),
+            Packet::PilotPosition(p) => p.fmt(f),
+            Packet::AtcPosition(p) => p.fmt(f),
+            Packet::TextMessage(p) => p.fmt(f),
+            Packet::ClientQuery(p) => p.write(f, "$CQ"),
+            Packet::ClientResponse(p) => p.write(f, "$CR"),
+            Packet::FlightPlan(p) => p.fmt(f),
+            Packet::Error(p) => p.fmt(f),
+            Packet::ServerIdentification(p) => p.fmt(f),
+            Packet::ClientIdentification(p) => p.fmt(f),
+            Packet::Kill(p) => p.fmt(f),
+            Packet::Unknown(line) => f.write_str(line),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(line: &str) -> Packet {
+        let packet = Packet::parse(line).unwrap();
+        assert_eq!(packet.to_string(), line);
+        assert_eq!(Packet::parse(&packet.to_string()).unwrap(), packet);
+        packet
+    }
+
+    #[test]
+    fn test_round_trip() {
+        round_trip("#AAEGLL_TWR:SERVER:Jane Doe:123456:secret:5:9");
+        round_trip("#AAEGLL_TWR:SERVER:Jane Doe:123456:secret:5");
+        round_trip("#APBAW123:SERVER:123456:secret:1:9:1:John Smith EGLL");
+        round_trip("#DABAW123:123456");
+        round_trip("#DPBAW123:123456");
+        round_trip("#DABAW123");
+        round_trip("@N:BAW123:7000:1:51.4775:-0.461389:1200:140:4290772992:25");
+        round_trip("@N:BAW123:7000:1:51.4775:-0.461389:1200:140:4290772992");
+        round_trip("%EGLL_TWR:18700:4:50:5:51.4775:-0.461389:0");
+        round_trip("%EGLL_TWR:18700:4:50:5:51.4775:-0.461389");
+        round_trip("#TMEGLL_TWR:BAW123:Cleared to land: runway 27L");
+        round_trip("$CQEGLL_TWR:BAW123:RN");
+        round_trip("$CRBAW123:EGLL_TWR:RN:John Smith:FS9:1");
+        round_trip("$FPBAW123:*A:I:B744:480:EGLL:1200:1200:FL350:KJFK:7:30:9:00:KBOS:/v/:DVR UL9 KONAN");
+        round_trip("$ERserver:BAW123:006:BAW123:Invalid callsign");
+        round_trip("$ERserver:BAW123:6:BAW123:Invalid callsign");
+        round_trip("$DISERVER:CLIENT:open-air fsd 0.1.0:abcdef");
+        round_trip("$IDBAW123:SERVER:1234:open-air:0:1:123456:0:abcdef");
+        round_trip("$!!SERVER:BAW123:Inappropriate callsign");
+    }
+
+    #[test]
+    fn test_parse_pilot_position() {
+        let packet = round_trip("@S:BAW123:2000:1:51.5:-0.5:35000:450:2:0");
+        let position = match packet {
+            Packet::PilotPosition(p) => p,
+            _ => panic!("expected pilot position"),
+        };
+
+        assert_eq!(position.mode, TransponderMode::Standby);
+        assert_eq!(position.altitude, 35000);
+        assert!(position.on_ground());
+        assert_eq!(position.heading(), 0.);
+    }
+
+    #[test]
+    fn test_parse_text_message() {
+        let packet = round_trip("#TMBAW123:@18700&@21800:hello");
+        let message = match packet {
+            Packet::TextMessage(m) => m,
+            _ => panic!("expected text message"),
+        };
+
+        assert!(!message.is_broadcast());
+        assert_eq!(message.frequencies().collect::<Vec<_>>(), vec![18700, 21800]);
+    }
+
+    #[test]
+    fn test_parse_unknown() {
+        let packet = round_trip("$AXBAW123:SERVER:METAR:EGLL");
+        assert_eq!(packet, Packet::Unknown("$AXBAW123:SERVER:METAR:EGLL".into()));
+        assert_eq!(Packet::parse("").unwrap(), Packet::Unknown(String::new()));
+    }
+
+    #[test]
+    fn test_parse_malformed() {
+        assert!(Packet::parse("@N:BAW123").is_err());
+        assert!(Packet::parse("#TM").is_err());
+
+        let err = Packet::parse("%EGLL_TWR:18700:4:50:5:51.4775:-0.461389:high").unwrap_err();
+        assert_eq!(err.to_string(), "invalid altitude \"high\": invalid digit found in string");
+    }
+}
@@ -0,0 +1,572 @@
+//! A minimal FSD server, suitable for local sessions and testing.
+//!
+//! The server tracks logged-in pilots and ATC, relays position updates between
+//! clients which are within visibility range of each other and routes text
+//! messages. It doesn't authenticate clients, any CID and password is accepted.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use anyhow::anyhow;
+use log::{debug, info, warn};
+use open_air::domain::coords::distance_nm;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio::sync::mpsc;
+
+use crate::packet::{self, Delete, Packet, ServerIdentification, SERVER};
+
+const ERROR_CALLSIGN_IN_USE: u32 = 1;
+const ERROR_INVALID_CALLSIGN: u32 = 2;
+const ERROR_ALREADY_REGISTERED: u32 = 3;
+const ERROR_INVALID_SOURCE: u32 = 5;
+const ERROR_NO_SUCH_CALLSIGN: u32 = 7;
+
+/// Configuration for an FSD server.
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// The name reported to clients in the server identification.
+    pub name: String,
+    /// The visibility range of pilots, in nautical miles.
+    pub pilot_range: f64,
+    /// The visibility range of ATC which haven't reported one, in nautical miles.
+    pub default_atc_range: f64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            name: format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+            pilot_range: 50.,
+            default_atc_range: 100.,
+        }
+    }
+}
+
+/// Whether a connected client is a pilot or controller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientRole {
+    Pilot,
+    Atc,
+}
+
+/// A summary of a logged-in client.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientSummary {
+    pub callsign: String,
+    pub cid: String,
+    pub real_name: String,
+    pub role: ClientRole,
+    /// The last reported position as `(latitude, longitude)`.
+    pub position: Option<(f64, f64)>,
+    /// The primary frequency of ATC, in kHz above 100MHz.
+    pub frequency: Option<u32>,
+    pub flight_plan: Option<packet::FlightPlan>,
+}
+
+struct ConnectedClient {
+    summary: ClientSummary,
+    range: f64,
+    sender: mpsc::UnboundedSender<String>,
+}
+
+#[derive(Default)]
+struct State {
+    clients: HashMap<String, ConnectedClient>,
+}
+
+fn is_valid_callsign(callsign: &str) -> bool {
+    !callsign.is_empty()
+        && callsign.len() <= 12
+        && callsign.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        && callsign != SERVER
+}
+
+impl State {
+    fn send_to(&self, callsign: &str, line: &str) -> bool {
+        match self.clients.get(callsign) {
+            Some(client) => {
+                let _ = client.sender.send(line.to_owned());
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn broadcast(&self, from: &str, line: &str, filter: impl Fn(&ConnectedClient) -> bool) {
+        for (callsign, client) in self.clients.iter() {
+            if callsign != from && filter(client) {
+                let _ = client.sender.send(line.to_owned());
+            }
+        }
+    }
+
+    /// Send a line to all clients which can see the given client and pass the
+    /// filter.
+    ///
+    /// Two clients can see each other when they're within the larger of their
+    /// visibility ranges.
+    fn broadcast_in_range(&self, from: &str, line: &str, filter: impl Fn(&ConnectedClient) -> bool) {
+        let sender = match self.clients.get(from) {
+            Some(x) => x,
+            None => return,
+        };
+        let position = match sender.summary.position {
+            Some(x) => x,
+            None => return,
+        };
+
+        self.broadcast(from, line, |client| {
+            filter(client) && client.summary.position.is_some_and(|other| {
+                distance_nm(position, other) <= sender.range.max(client.range)
+            })
+        });
+    }
+}
+
+/// A handle to inspect the state of a running server.
+#[derive(Clone)]
+pub struct ServerHandle {
+    state: Arc<Mutex<State>>,
+}
+
+impl ServerHandle {
+    /// List all of the logged-in clients.
+    pub fn clients(&self) -> Vec<ClientSummary> {
+        let state = self.state.lock().unwrap();
+        let mut clients = state.clients.values()
+            .map(|c| c.summary.clone())
+            .collect::<Vec<_>>();
+        clients.sort_by(|a, b| a.callsign.cmp(&b.callsign));
+        clients
+    }
+
+    /// Get a logged-in client by callsign.
+    pub fn client(&self, callsign: &str) -> Option<ClientSummary> {
+        let state = self.state.lock().unwrap();
+        state.clients.get(callsign).map(|c| c.summary.clone())
+    }
+}
+
+/// An FSD server listening for connections.
+pub struct Server {
+    config: Arc<ServerConfig>,
+    listener: TcpListener,
+    state: Arc<Mutex<State>>,
+}
+
+impl Server {
+    /// Bind a new server to the given address.
+    pub async fn bind(addr: impl ToSocketAddrs, config: ServerConfig) -> anyhow::Result<Server> {
+        let listener = TcpListener::bind(addr).await?;
+        Ok(Server {
+            config: Arc::new(config),
+            listener,
+            state: Default::default(),
+        })
+    }
+
+    /// Get the address the server is listening on.
+    pub fn local_addr(&self) -> anyhow::Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Get a handle which can be used to inspect the server once it's running.
+    pub fn handle(&self) -> ServerHandle {
+        ServerHandle {
+            state: self.state.clone(),
+        }
+    }
+
+    /// Accept and serve clients until an error occurs.
+    pub async fn run(self) -> anyhow::Result<()> {
+        loop {
+            let (stream, addr) = self.listener.accept().await?;
+            info!("accepted FSD connection from {}", addr);
+
+            let session = Session {
+                config: self.config.clone(),
+                state: self.state.clone(),
+                callsign: None,
+            };
+            tokio::spawn(async move {
+                if let Err(err) = session.run(stream).await {
+                    warn!("FSD connection from {} failed: {}", addr, err);
+                }
+            });
+        }
+    }
+}
+
+struct Session {
+    config: Arc<ServerConfig>,
+    state: Arc<Mutex<State>>,
+    callsign: Option<String>,
+}
+
+impl Session {
+    async fn run(mut self, stream: TcpStream) -> anyhow::Result<()> {
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+
+        let challenge = format!("{:08x}", rand_challenge());
+        let ident = Packet::ServerIdentification(ServerIdentification {
+            from: SERVER.into(),
+            to: "CLIENT".into(),
+            version: self.config.name.clone(),
+            challenge,
+        });
+        let _ = sender.send(ident.to_string());
+
+        let result = loop {
+            tokio::select! {
+                line = lines.next_line() => {
+                    let line = match line {
+                        Ok(Some(line)) => line,
+                        Ok(None) => break Ok(()),
+                        Err(err) => break Err(err.into()),
+                    };
+                    debug!("< {}", line);
+
+                    match self.handle_line(&line, &sender) {
+                        Ok(true) => {}
+                        Ok(false) => break Ok(()),
+                        Err(err) => {
+                            warn!("bad packet from {:?}: {}", self.callsign, err);
+                        }
+                    }
+                }
+                line = receiver.recv() => {
+                    // We always hold a sender, so this can't be `None`.
+                    let line = line.unwrap();
+                    debug!("> {}", line);
+                    if let Err(err) = writer.write_all(format!("{}\r\n", line).as_bytes()).await {
+                        break Err(err.into());
+                    }
+                }
+            }
+        };
+
+        // Flush anything queued before the connection closed, such as errors.
+        while let Ok(line) = receiver.try_recv() {
+            let _ = writer.write_all(format!("{}\r\n", line).as_bytes()).await;
+        }
+
+        self.logout();
+        result
+    }
+
+    fn error(sender: &mpsc::UnboundedSender<String>, to: &str, code: u32, parameter: &str, message: &str) {
+        let error = Packet::Error(packet::Error {
+            from: SERVER.into(),
+            to: to.into(),
+            code: format!("{:03}", code),
+            parameter: parameter.into(),
+            message: message.into(),
+        });
+        let _ = sender.send(error.to_string());
+    }
+
+    fn login(&mut self, summary: ClientSummary, sender: &mpsc::UnboundedSender<String>, line: &str) -> bool {
+        if self.callsign.is_some() {
+            Self::error(sender, &summary.callsign, ERROR_ALREADY_REGISTERED, "", "Already registered");
+            return true;
+        }
+
+        if !is_valid_callsign(&summary.callsign) {
+            Self::error(sender, &summary.callsign, ERROR_INVALID_CALLSIGN, &summary.callsign, "Invalid callsign");
+            return false;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        if state.clients.contains_key(&summary.callsign) {
+            Self::error(sender, &summary.callsign, ERROR_CALLSIGN_IN_USE, &summary.callsign, "Callsign in use");
+            return false;
+        }
+
+        info!("{} logged in as {:?}", summary.callsign, summary.role);
+        let range = match summary.role {
+            ClientRole::Pilot => self.config.pilot_range,
+            ClientRole::Atc => self.config.default_atc_range,
+        };
+        let callsign = summary.callsign.clone();
+        state.broadcast(&callsign, line, |_| true);
+        state.clients.insert(callsign.clone(), ConnectedClient {
+            summary,
+            range,
+            sender: sender.clone(),
+        });
+        self.callsign = Some(callsign);
+        true
+    }
+
+    fn logout(&mut self) {
+        let callsign = match self.callsign.take() {
+            Some(x) => x,
+            None => return,
+        };
+
+        let mut state = self.state.lock().unwrap();
+        if let Some(client) = state.clients.remove(&callsign) {
+            info!("{} logged out", callsign);
+            let delete = Delete {
+                callsign: callsign.clone(),
+                cid: Some(client.summary.cid),
+            };
+            let packet = match client.summary.role {
+                ClientRole::Pilot => Packet::DeletePilot(delete),
+                ClientRole::Atc => Packet::DeleteAtc(delete),
+            };
+            state.broadcast(&callsign, &packet.to_string(), |_| true);
+        }
+    }
+
+    /// Handle a single incoming line, returning whether the connection should stay open.
+    fn handle_line(&mut self, line: &str, sender: &mpsc::UnboundedSender<String>) -> anyhow::Result<bool> {
+        let packet = Packet::parse(line)?;
+
+        match packet {
+            Packet::ClientIdentification(_) => return Ok(true),
+            Packet::AddPilot(add) => {
+                let summary = ClientSummary {
+                    callsign: add.callsign,
+                    cid: add.cid,
+                    real_name: add.real_name,
+                    role: ClientRole::Pilot,
+                    position: None,
+                    frequency: None,
+                    flight_plan: None,
+                };
+                return Ok(self.login(summary, sender, line));
+            }
+            Packet::AddAtc(add) => {
+                let summary = ClientSummary {
+                    callsign: add.callsign,
+                    cid: add.cid,
+                    real_name: add.real_name,
+                    role: ClientRole::Atc,
+                    position: None,
+                    frequency: None,
+                    flight_plan: None,
+                };
+                return Ok(self.login(summary, sender, line));
+            }
+            _ => {}
+        }
+
+        let callsign = match self.callsign.clone() {
+            Some(x) => x,
+            None => return Err(anyhow!("packet before login: {}", line)),
+        };
+
+        if let Some(from) = packet.sender() {
+            if from != callsign {
+                Self::error(sender, &callsign, ERROR_INVALID_SOURCE, from, "Invalid source callsign");
+                return Ok(true);
+            }
+        }
+
+        let mut state = self.state.lock().unwrap();
+        match packet {
+            Packet::DeletePilot(_) | Packet::DeleteAtc(_) => {
+                drop(state);
+                self.logout();
+                return Ok(false);
+            }
+            Packet::PilotPosition(position) => {
+                if let Some(client) = state.clients.get_mut(&callsign) {
+                    client.summary.position = Some((position.latitude, position.longitude));
+                }
+                state.broadcast_in_range(&callsign, line, |_| true);
+            }
+            Packet::AtcPosition(position) => {
+                if let Some(client) = state.clients.get_mut(&callsign) {
+                    client.summary.position = Some((position.latitude, position.longitude));
+                    client.summary.frequency = Some(position.frequency);
+                    if position.visual_range > 0 {
+                        client.range = position.visual_range as f64;
+                    }
+                }
+                state.broadcast_in_range(&callsign, line, |_| true);
+            }
+            Packet::TextMessage(message) => {
+                if message.is_broadcast() {
+                    state.broadcast(&callsign, line, |_| true);
+                } else if message.to == "*S" {
+                    // We don't track supervisors, so there's nobody to send these to.
+                } else if message.to.starts_with('@') {
+                    // ATC only get messages on their primary frequency. Pilots don't
+                    // report what they're tuned to, so they get every frequency
+                    // message in range and leave it to their client to filter.
+                    let frequencies = message.frequencies().collect::<Vec<_>>();
+                    state.broadcast_in_range(&callsign, line, |client| {
+                        client.summary.frequency.is_none_or(|f| frequencies.contains(&f))
+                    });
+                } else if !state.send_to(&message.to, line) {
+                    Self::error(sender, &callsign, ERROR_NO_SUCH_CALLSIGN, &message.to, "No such callsign");
+                }
+            }
+            Packet::FlightPlan(flight_plan) => {
+                if let Some(client) = state.clients.get_mut(&callsign) {
+                    client.summary.flight_plan = Some(*flight_plan);
+                }
+                state.broadcast(&callsign, line, |c| c.summary.role == ClientRole::Atc);
+            }
+            Packet::ClientQuery(query) | Packet::ClientResponse(query) => {
+                if query.to == SERVER {
+                    // Server queries aren't supported.
+                } else if query.to.starts_with('@') || query.to.starts_with('*') {
+                    state.broadcast(&callsign, line, |_| true);
+                } else if !state.send_to(&query.to, line) {
+                    Self::error(sender, &callsign, ERROR_NO_SUCH_CALLSIGN, &query.to, "No such callsign");
+                }
+            }
+            Packet::Unknown(_) => {
+                // Relay directed packets we don't understand, so that clients can
+                // use newer packets without server support.
+                let to = line.get(3..).and_then(|body| body.split(':').nth(1));
+                if let Some(to) = to {
+                    state.send_to(to, line);
+                }
+            }
+            _ => {}
+        }
+
+        Ok(true)
+    }
+}
+
+fn rand_challenge() -> u32 {
+    use std::collections::hash_map::RandomState;
+    use std::hash::{BuildHasher, Hasher};
+
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64));
+    hasher.finish() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::time::timeout;
+
+    use crate::client::{Client, ClientConfig, ClientKind, ConnectionState, Event};
+
+    use super::*;
+
+    async fn start() -> (SocketAddr, ServerHandle) {
+        let server = Server::bind("127.0.0.1:0", ServerConfig::default()).await.unwrap();
+        let addr = server.local_addr().unwrap();
+        let handle = server.handle();
+        tokio::spawn(server.run());
+        (addr, handle)
+    }
+
+    async fn login(addr: SocketAddr, kind: ClientKind, callsign: &str) -> Client {
+        let config = ClientConfig::new(kind, callsign, "123456", "secret");
+        let mut client = Client::connect(addr, config).await.unwrap();
+        assert_eq!(client.next_event().await, Some(Event::State(ConnectionState::Connecting)));
+        assert_eq!(client.next_event().await, Some(Event::State(ConnectionState::LoggedIn)));
+        client
+    }
+
+    async fn next_packet(client: &mut Client) -> Packet {
+        match timeout(Duration::from_secs(5), client.next_event()).await.unwrap() {
+            Some(Event::Packet(packet)) => packet,
+            other => panic!("expected packet, got {:?}", other),
+        }
+    }
+
+    async fn wait_for_clients(handle: &ServerHandle, count: usize) {
+        while handle.clients().len() != count {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_relay() {
+        let (addr, handle) = start().await;
+
+        let mut atc = login(addr, ClientKind::Atc, "EGLL_TWR").await;
+        wait_for_clients(&handle, 1).await;
+        atc.send(Packet::parse("%EGLL_TWR:18700:4:30:5:51.4775:-0.461389:0").unwrap()).unwrap();
+
+        let pilot = login(addr, ClientKind::Pilot { sim_type: 1 }, "BAW123").await;
+        let far_pilot = login(addr, ClientKind::Pilot { sim_type: 1 }, "QFA1").await;
+        wait_for_clients(&handle, 3).await;
+
+        assert!(matches!(next_packet(&mut atc).await, Packet::AddPilot(p) if p.callsign == "BAW123"));
+        assert!(matches!(next_packet(&mut atc).await, Packet::AddPilot(p) if p.callsign == "QFA1"));
+
+        far_pilot.send(Packet::parse("@N:QFA1:7000:1:-33.9461:151.1772:0:0:0:0").unwrap()).unwrap();
+        pilot.send(Packet::parse("@N:BAW123:7000:1:51.5:-0.5:1200:140:0:0").unwrap()).unwrap();
+        match next_packet(&mut atc).await {
+            Packet::PilotPosition(p) => assert_eq!(p.callsign, "BAW123"),
+            other => panic!("expected pilot position, got {:?}", other),
+        }
+
+        pilot.send(Packet::parse("#TMBAW123:EGLL_TWR:hello").unwrap()).unwrap();
+        match next_packet(&mut atc).await {
+            Packet::TextMessage(m) => assert_eq!(m.message, "hello"),
+            other => panic!("expected text message, got {:?}", other),
+        }
+
+        let summary = handle.client("EGLL_TWR").unwrap();
+        assert_eq!(summary.role, ClientRole::Atc);
+        assert_eq!(summary.frequency, Some(18700));
+
+        far_pilot.disconnect();
+        assert!(matches!(next_packet(&mut atc).await, Packet::DeletePilot(p) if p.callsign == "QFA1"));
+        wait_for_clients(&handle, 2).await;
+    }
+
+    async fn next_text_message(client: &mut Client) -> String {
+        loop {
+            if let Packet::TextMessage(m) = next_packet(client).await {
+                return m.message;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_frequency_messages() {
+        let (addr, handle) = start().await;
+
+        let mut tower = login(addr, ClientKind::Atc, "EGLL_TWR").await;
+        wait_for_clients(&handle, 1).await;
+        tower.send(Packet::parse("%EGLL_TWR:18700:4:30:5:51.4775:-0.461389:0").unwrap()).unwrap();
+
+        let mut approach = login(addr, ClientKind::Atc, "EGLL_APP").await;
+        wait_for_clients(&handle, 2).await;
+        approach.send(Packet::parse("%EGLL_APP:19725:5:30:5:51.4775:-0.461389:0").unwrap()).unwrap();
+
+        let pilot = login(addr, ClientKind::Pilot { sim_type: 1 }, "BAW123").await;
+        wait_for_clients(&handle, 3).await;
+        pilot.send(Packet::parse("@N:BAW123:7000:1:51.5:-0.5:1200:140:0:0").unwrap()).unwrap();
+        pilot.send(Packet::parse("#TMBAW123:@18700:on tower").unwrap()).unwrap();
+        pilot.send(Packet::parse("#TMBAW123:EGLL_APP:direct").unwrap()).unwrap();
+
+        assert_eq!(next_text_message(&mut tower).await, "on tower");
+        assert_eq!(next_text_message(&mut approach).await, "direct");
+    }
+
+    #[tokio::test]
+    async fn test_callsign_in_use() {
+        let (addr, handle) = start().await;
+
+        let _first = login(addr, ClientKind::Atc, "EGLL_TWR").await;
+        wait_for_clients(&handle, 1).await;
+
+        let mut second = login(addr, ClientKind::Atc, "EGLL_TWR").await;
+        match second.next_event().await {
+            Some(Event::Error(err)) => assert_eq!(err.code_number(), Some(ERROR_CALLSIGN_IN_USE)),
+            other => panic!("expected error, got {:?}", other),
+        }
+        assert_eq!(second.next_event().await, Some(Event::State(ConnectionState::Disconnected)));
+    }
+}
@@ -1,16 +1,65 @@
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::fmt::{self, Display, Formatter};
+use std::io::Write;
 use std::str::Split;
+use std::sync::Arc;
 
 use anyhow::anyhow;
+use open_air::domain::coords;
 use open_air::domain::viewer::Colour;
+use serde::Serialize;
+
+/// Where a statement was read from.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct Location {
+    /// The path of the file within the sector package, or empty if unknown.
+    pub path: Arc<str>,
+    /// The line number, starting from 1.
+    pub line: usize,
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "line {}", self.line)
+        } else {
+            write!(f, "{}:{}", self.path, self.line)
+        }
+    }
+}
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// A single line of a section.
+///
+/// Statements compare by their contents alone, regardless of where they came from.
+#[derive(Debug, Clone)]
 pub struct Statement {
     contents: String,
+    location: Option<Location>,
+}
+
+impl PartialEq for Statement {
+    fn eq(&self, other: &Statement) -> bool {
+        self.contents == other.contents
+    }
+}
+
+impl Eq for Statement {}
+
+impl PartialOrd for Statement {
+    fn partial_cmp(&self, other: &Statement) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Statement {
+    fn cmp(&self, other: &Statement) -> Ordering {
+        self.contents.cmp(&other.contents)
+    }
 }
 
 impl Statement {
     /// Construct a statement from a string.
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(src: impl Into<String>) -> Statement {
         let mut contents = src.into();
         if contents.ends_with(";") {
@@ -19,9 +68,29 @@ impl Statement {
 
         Statement {
             contents,
+            location: None,
         }
     }
 
+    /// Set where this statement was read from.
+    pub fn with_location(mut self, location: Location) -> Statement {
+        self.location = Some(location);
+        self
+    }
+
+    /// Get where this statement was read from, if it was read from a file.
+    pub fn location(&self) -> Option<&Location> {
+        self.location.as_ref()
+    }
+
+    /// Parse this statement, adding its location to any error.
+    pub fn parse_with<T>(&self, parse: impl FnOnce(&Statement) -> anyhow::Result<T>) -> anyhow::Result<T> {
+        parse(self).map_err(|err| match &self.location {
+            Some(location) => err.context(location.clone()),
+            None => err,
+        })
+    }
+
     /// Get the string value of this statement.
     pub fn as_str(&self) -> &str {
         &self.contents
@@ -31,23 +100,123 @@ impl Statement {
     pub fn parts(&self) -> Split<'_, char> {
         self.contents.split(';')
     }
+
+    /// Construct a statement from its parts.
+    pub fn from_parts<'a>(parts: impl IntoIterator<Item=&'a str>) -> Statement {
+        Statement {
+            contents: parts.into_iter().collect::<Vec<_>>().join(";"),
+            location: None,
+        }
+    }
+
+    /// Whether the statement needs a trailing `;` to be read back as it is.
+    ///
+    /// Lines are trimmed and lose one trailing `;` when parsed, so anything
+    /// ending in whitespace or `;` (or nothing at all) needs protecting.
+    fn needs_terminator(&self) -> bool {
+        self.contents.chars().last()
+            .is_none_or(|c| c == ';' || c.is_whitespace())
+    }
+
+    /// Check that the statement would be read back as it is once written.
+    fn check_writable(&self) -> anyhow::Result<()> {
+        let contents = &self.contents;
+        let problem = if contents.contains(['\r', '\n']) {
+            "contains a line break"
+        } else if contents.starts_with(char::is_whitespace) {
+            "starts with whitespace"
+        } else if contents.starts_with('[') {
+            "would be read as a section header"
+        } else if contents.starts_with("//") {
+            "would be read as a comment"
+        } else {
+            return Ok(());
+        };
+
+        let err = anyhow!("statement {:?} {}", contents, problem);
+        Err(match &self.location {
+            Some(location) => err.context(location.clone()),
+            None => err,
+        })
+    }
+}
+
+impl Display for Statement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.needs_terminator() {
+            write!(f, "{};", self.contents)
+        } else {
+            write!(f, "{}", self.contents)
+        }
+    }
 }
 
 #[derive(Debug, Clone, Default)]
 pub struct Section {
+    name: String,
     statements: Vec<Statement>,
 }
 
 impl Section {
+    /// Create a new empty section.
+    pub fn new(name: impl Into<String>) -> Section {
+        Section {
+            name: name.into(),
+            statements: Vec::new(),
+        }
+    }
+
+    /// The name of this section, which is empty for statements before the first header.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
     /// Fetch the list of statements contained in this section.
     pub fn statements(&self) -> &[Statement] {
         &self.statements
     }
+
+    /// Fetch the list of statements for editing.
+    pub fn statements_mut(&mut self) -> &mut Vec<Statement> {
+        &mut self.statements
+    }
+
+    /// Check that the section would be read back as it is once written.
+    fn check_writable(&self) -> anyhow::Result<()> {
+        let name = &self.name;
+        if name.contains(['\r', '\n']) || name.starts_with(char::is_whitespace) || name.ends_with(char::is_whitespace) {
+            return Err(anyhow!("section name {:?} can't be written", name));
+        }
+
+        for statement in &self.statements {
+            statement.check_writable()?;
+        }
+        Ok(())
+    }
 }
 
+impl Display for Section {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if !self.name.is_empty() {
+            write!(f, "[{}]\r\n", self.name)?;
+        }
+
+        for statement in &self.statements {
+            write!(f, "{}\r\n", statement)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A file in Aurora's generic data format, made up of named sections of statements.
+///
+/// Sections are kept in the order they were parsed or created, and a file can
+/// be written back out with [`File::write_to`], or its `Display` implementation
+/// which doesn't check that the result will be read back the same.
 #[derive(Debug, Clone, Default)]
 pub struct File {
-    sections: HashMap<String, Section>,
+    sections: Vec<Section>,
 }
 
 impl File {
@@ -56,60 +225,122 @@ impl File {
         Default::default()
     }
 
-    /// Return the sections map
-    pub fn sections(&self) -> &HashMap<String, Section> {
+    /// Return the sections, in order.
+    ///
+    /// A section which appears more than once in the source is returned once
+    /// for each occurrence.
+    pub fn sections(&self) -> &[Section] {
         &self.sections
     }
 
-    /// Get the section with the given key.
+    /// Get the first section with the given key.
+    ///
+    /// Use [`File::statements`] to see the statements from every occurrence
+    /// of a repeated section.
     pub fn section(&self, key: &str) -> Option<&Section> {
-        self.sections.get(key)
+        self.sections.iter().find(|s| s.name == key)
+    }
+
+    /// Iterate over every occurrence of the section with the given key.
+    pub fn sections_named<'a>(&'a self, key: &'a str) -> impl Iterator<Item=&'a Section> + 'a {
+        self.sections.iter().filter(move |s| s.name == key)
+    }
+
+    /// Iterate over the statements in every occurrence of the section with the given key.
+    pub fn statements<'a>(&'a self, key: &'a str) -> impl Iterator<Item=&'a Statement> + 'a {
+        self.sections_named(key).flat_map(|s| s.statements.iter())
     }
 
     /// Get or create a section with the given name.
+    ///
+    /// If the section is repeated, the first occurrence is returned. New
+    /// sections are added to the end of the file.
     pub fn section_mut(&mut self, key: &str) -> &mut Section {
-        if !self.sections.contains_key(key) {
-            self.sections.insert(key.to_owned(), Default::default());
-        }
+        let idx = match self.sections.iter().position(|s| s.name == key) {
+            Some(idx) => idx,
+            None => {
+                self.sections.push(Section::new(key));
+                self.sections.len() - 1
+            }
+        };
 
-        self.sections.get_mut(key).unwrap()
+        &mut self.sections[idx]
     }
 
+    /// Remove every occurrence of a section, returning their statements as one section.
     pub fn take_section(&mut self, name: &str) -> Option<Section> {
-        self.sections.remove(name)
+        let mut taken: Option<Section> = None;
+        self.sections.retain_mut(|section| {
+            if section.name != name {
+                return true;
+            }
+
+            match &mut taken {
+                Some(taken) => taken.statements.append(&mut section.statements),
+                None => taken = Some(std::mem::take(section)),
+            }
+            false
+        });
+        taken
+    }
+
+    /// Merge repeated sections into their first occurrence.
+    pub fn merge_duplicates(&mut self) {
+        let mut merged: Vec<Section> = Vec::with_capacity(self.sections.len());
+        for mut section in self.sections.drain(..) {
+            match merged.iter_mut().find(|s| s.name == section.name) {
+                Some(existing) => existing.statements.append(&mut section.statements),
+                None => merged.push(section),
+            }
+        }
+        self.sections = merged;
     }
 
     /// Parse a sector file from a string.
+    ///
+    /// Sections are kept in source order, and repeated sections are kept separately.
     pub fn parse(src: &str) -> anyhow::Result<File> {
-        let mut sections = HashMap::new();
-        let mut section: Option<(String, Section)> = None;
+        File::parse_named(src, "")
+    }
+
+    /// Parse a sector file from a string, recording `path` as the location of each statement.
+    pub fn parse_named(src: &str, path: &str) -> anyhow::Result<File> {
+        let path: Arc<str> = Arc::from(path);
+        let mut sections: Vec<Section> = Vec::new();
+        let mut section: Option<Section> = None;
 
-        let mut flush_section = |section: &mut Option<(String, Section)>| {
-            if let Some((key, section)) = section.take() {
-                sections.insert(key.to_owned(), section);
+        let mut flush_section = |section: &mut Option<Section>| {
+            if let Some(section) = section.take() {
+                sections.push(section);
             }
         };
 
         let lines = src.lines()
             .map(|l| l.trim())
-            .filter(|v| !v.is_empty() && !v.starts_with("//"));
-        for line in lines {
+            .enumerate()
+            .filter(|(_, v)| !v.is_empty() && !v.starts_with("//"));
+        for (idx, line) in lines {
+            let location = Location {
+                path: path.clone(),
+                line: idx + 1,
+            };
+
             if line.starts_with("[") {
                 // New section
                 if !line.ends_with("]") {
-                    Err(anyhow!("section must end with ], got: {}", line))?;
+                    Err(anyhow!("{}: section must end with ], got: {}", location, line))?;
                 }
 
                 flush_section(&mut section);
                 let section_name = &line[1..line.len() - 1];
-                section = Some((section_name.to_owned(), Default::default()));
-            } else if let Some((_, ref mut section)) = section {
+                section = Some(Section::new(section_name));
+            } else if let Some(ref mut section) = section {
                 // Statement
-                section.statements.push(Statement::from_str(line));
+                section.statements.push(Statement::from_str(line).with_location(location));
             } else {
                 let mut new_section = Section::default();
-                new_section.statements.push(Statement::from_str(line));
-                section = Some((String::new(), new_section));
+                new_section.statements.push(Statement::from_str(line).with_location(location));
+                section = Some(new_section);
             }
         }
 
@@ -120,61 +351,58 @@ impl File {
     }
 }
 
-fn parse_long_or_lat(src: &str, pos: char, neg: char) -> anyhow::Result<f64> {
-    let is_decimal = match src.chars().next() {
-        Some('-') => true,
-        Some(x) => x.is_digit(10),
-        None => false,
-    };
-
-    if is_decimal {
-        Ok(src.parse()?)
-    } else {
-        let mut chars = src.chars();
-        let multiplier = match chars.next() {
-            Some(x) if x == pos => 1.0f64,
-            Some(x) if x == neg => -1.0f64,
-            Some(x) => return Err(anyhow!("unexpected prefix {}", x)),
-            None => return Err(anyhow!("missing prefix character")),
-        };
-
-        let (degrees, minutes, seconds) = if src.contains('.') {
-            let mut parts = chars.as_str().splitn(3, '.');
-
-            let degrees: f64 = parts.next()
-                .ok_or_else(|| anyhow!("missing degrees"))
-                .and_then(|v| Ok(str::parse(v)?))?;
-            let minutes: f64 = parts.next()
-                .ok_or_else(|| anyhow!("missing minutes"))
-                .and_then(|v| Ok(str::parse(v)?))?;
-            let seconds: f64 = parts.next()
-                .ok_or_else(|| anyhow!("missing seconds"))
-                .and_then(|v| Ok(str::parse(v)?))?;
-
-            if let Some(part) = parts.next() {
-                Err(anyhow!("unexpected characters: {}", part))?;
+impl File {
+    /// Write the file out in Aurora's format.
+    ///
+    /// Unlike the `Display` implementation, this fails rather than writing
+    /// anything which would be read back differently: statements starting with
+    /// whitespace, `[` or `//`, line breaks, or statements outside a section
+    /// which follow a header.
+    pub fn write_to(&self, writer: &mut impl Write) -> anyhow::Result<()> {
+        let mut seen_header = false;
+        for section in &self.sections {
+            if section.name.is_empty() && seen_header && !section.statements.is_empty() {
+                let err = anyhow!("statements outside a section must come before the first header");
+                return Err(match section.statements[0].location() {
+                    Some(location) => err.context(location.clone()),
+                    None => err,
+                });
             }
+            seen_header |= !section.name.is_empty();
+            section.check_writable()?;
+        }
 
-            (degrees, minutes, seconds)
-        } else if src.len() == 11 {
-            let degrees: f64 = src[1..4].parse()?;
-            let minutes: f64 = src[4..6].parse()?;
-            let seconds = src[6..].parse::<f64>()? / 1000.;
-            (degrees, minutes, seconds)
-        } else {
-            return Err(anyhow!("unsupported coordinate format: {}", src));
-        };
+        writer.write_all(self.to_string().as_bytes())?;
+        Ok(())
+    }
+}
 
-        Ok((degrees + (minutes / 60f64) + (seconds / 3600f64)) * multiplier)
+impl Display for File {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        // Sections are written in order, except that statements outside a
+        // section can only be written before the first header.
+        let first_header = self.sections.iter()
+            .position(|s| !s.name.is_empty())
+            .unwrap_or(self.sections.len());
+        let (before, after) = self.sections.split_at(first_header);
+        let loose = after.iter().filter(|s| s.name.is_empty());
+        for section in before.iter().chain(loose) {
+            write!(f, "{}", section)?;
+        }
+        for section in after.iter().filter(|s| !s.name.is_empty()) {
+            write!(f, "{}", section)?;
+        }
+
+        Ok(())
     }
 }
 
 pub fn parse_latitude(src: &str) -> anyhow::Result<f64> {
-    parse_long_or_lat(src, 'N', 'S')
+    Ok(coords::parse_latitude(src)?)
 }
 
 pub fn parse_longitude(src: &str) -> anyhow::Result<f64> {
-    parse_long_or_lat(src, 'E', 'W')
+    Ok(coords::parse_longitude(src)?)
 }
 
 pub fn parse_colour(src: &str) -> anyhow::Result<Colour> {
@@ -226,4 +454,127 @@ mod test {
         assert_eq!(stmts[0], Statement::from_str("My;Statement;1  "));
         assert_eq!(stmts[0].parts().nth(2), Some("1  "));
     }
+
+    #[test]
+    fn test_write() {
+        const SOURCE: &str = "
+            loose;statement
+            [FIXES]
+            ABC;N051.00.00.000;W001.00.00.000;
+            trailing space ;
+            empty;last;;
+            ;
+            [AIRPORT]
+            EGLL;N051.28.39.000;W000.27.41.000;LONDON HEATHROW
+        ";
+
+        let file = File::parse(SOURCE).unwrap();
+        let written = file.to_string();
+        assert!(written.starts_with("loose;statement\r\n[FIXES]\r\n"));
+
+        let reparsed = File::parse(&written).unwrap();
+        let names = reparsed.sections().iter().map(|s| s.name()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["", "FIXES", "AIRPORT"]);
+        for (original, reparsed) in file.sections().iter().zip(reparsed.sections()) {
+            assert_eq!(original.statements(), reparsed.statements());
+        }
+
+        let fixes = reparsed.section("FIXES").unwrap().statements();
+        assert_eq!(fixes[1].as_str(), "trailing space ");
+        assert_eq!(fixes[2].as_str(), "empty;last;");
+        assert_eq!(fixes[3].as_str(), "");
+    }
+
+    #[test]
+    fn test_edit() {
+        let mut file = File::new();
+        file.section_mut("VOR").statements_mut()
+            .push(Statement::from_parts(["LON", "113.600", "N051.29.12.000", "W000.28.00.000"]));
+        file.section_mut("INFO").statements_mut().push(Statement::from_str("Test"));
+
+        assert_eq!(file.to_string(),
+                   "[VOR]\r\nLON;113.600;N051.29.12.000;W000.28.00.000\r\n[INFO]\r\nTest\r\n");
+
+        let mut written = Vec::new();
+        file.write_to(&mut written).unwrap();
+        assert_eq!(written, file.to_string().into_bytes());
+    }
+
+    #[test]
+    fn test_write_checked() {
+        let write = |file: &File| {
+            let mut written = Vec::new();
+            file.write_to(&mut written).map(|_| written)
+        };
+
+        for contents in ["[FIXES]", "// not a comment", "  indented", "two\nlines"] {
+            let mut file = File::new();
+            file.section_mut("FIXES").statements_mut().push(Statement::from_str(contents));
+            assert!(write(&file).is_err(), "{:?} was written", contents);
+        }
+
+        let file = File::parse_named("[FIXES]\nABC;1;2\n", "fixes.fix").unwrap();
+        let mut edited = file.clone();
+        edited.section_mut("FIXES").statements_mut()[0] = Statement::from_str("[ABC]")
+            .with_location(file.section("FIXES").unwrap().statements()[0].location().unwrap().clone());
+        let err = write(&edited).unwrap_err();
+        assert_eq!(format!("{:#}", err), "fixes.fix:2: statement \"[ABC]\" would be read as a section header");
+
+        // Loose statements can't follow a header, so only the unchecked form moves them.
+        let mut file = File::new();
+        file.section_mut("FIXES").statements_mut().push(Statement::from_str("A;1;2"));
+        file.section_mut("").statements_mut().push(Statement::from_str("loose"));
+        assert!(write(&file).is_err());
+        assert_eq!(file.to_string(), "loose\r\n[FIXES]\r\nA;1;2\r\n");
+    }
+
+    #[test]
+    fn test_locations() {
+        const SOURCE: &str = "[FIXES]\r\n// Comment\r\n\r\nABC;N051.00.00.000\r\n";
+
+        let file = File::parse_named(SOURCE, "Include/EG/fixes.fix").unwrap();
+        let statement = &file.section("FIXES").unwrap().statements()[0];
+        let location = statement.location().unwrap();
+        assert_eq!(location.line, 4);
+        assert_eq!(location.to_string(), "Include/EG/fixes.fix:4");
+
+        let err = statement.parse_with(|_| -> anyhow::Result<()> { Err(anyhow::anyhow!("missing longitude")) })
+            .unwrap_err();
+        assert_eq!(format!("{:#}", err), "Include/EG/fixes.fix:4: missing longitude");
+
+        let err = File::parse("\n[FIXES").unwrap_err();
+        assert_eq!(err.to_string(), "line 2: section must end with ], got: [FIXES");
+    }
+
+    #[test]
+    fn test_duplicate_sections() {
+        const SOURCE: &str = "
+            [FIXES]
+            A;1;2
+            [VOR]
+            B;3;4
+            [FIXES]
+            C;5;6
+        ";
+
+        let mut file = File::parse(SOURCE).unwrap();
+        let names = file.sections().iter().map(|s| s.name()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["FIXES", "VOR", "FIXES"]);
+        assert_eq!(file.section("FIXES").unwrap().statements().len(), 1);
+        assert_eq!(file.sections_named("FIXES").count(), 2);
+
+        let fixes = file.statements("FIXES").map(|s| s.as_str()).collect::<Vec<_>>();
+        assert_eq!(fixes, vec!["A;1;2", "C;5;6"]);
+        assert_eq!(File::parse(&file.to_string()).unwrap().sections().len(), 3);
+
+        let mut merged = file.clone();
+        merged.merge_duplicates();
+        let names = merged.sections().iter().map(|s| s.name()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["FIXES", "VOR"]);
+        assert_eq!(merged.section("FIXES").unwrap().statements().len(), 2);
+
+        let taken = file.take_section("FIXES").unwrap();
+        assert_eq!(taken.statements().len(), 2);
+        assert_eq!(file.sections().len(), 1);
+    }
 }
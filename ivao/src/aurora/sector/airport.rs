@@ -1,7 +1,7 @@
 use anyhow::anyhow;
 
-use crate::aurora::gdf::Statement;
-use crate::aurora::sector::parsing::parse_string_position;
+use crate::aurora::gdf::{Location, Statement};
+use crate::aurora::sector::parsing::{parse_position, Position};
 use open_air::domain;
 use crate::aurora::sector::Sector;
 use std::collections::VecDeque;
@@ -13,9 +13,10 @@ pub struct Airport {
     pub identifier: String,
     pub elevation: f64,
     pub transition_altitude: Option<f64>,
-    pub geo_position: (String, String),
+    pub geo_position: Position,
     pub name: String,
     pub hide_tag: bool,
+    pub location: Option<Location>,
 }
 
 impl Airport {
@@ -29,14 +30,14 @@ impl Airport {
             .ok_or_else(|| anyhow!("missing elevation"))?
             .parse::<f64>()?;
         let transition_altitude = parts.next()
-            .and_then(|v| if v == "" { None } else { Some(v) })
+            .filter(|v| !v.is_empty())
             .map(|v| v.parse::<f64>())
             .transpose()?;
-        let geo_position = parse_string_position(&mut parts)?;
+        let geo_position = parse_position(&mut parts)?;
         let name = parts.next()
             .ok_or_else(|| anyhow!("missing airfield name"))?
             .to_owned();
-        let hide_tag = parts.next().map_or(false, |v| v == "1");
+        let hide_tag = parts.next() == Some("1");
 
         Ok(Airport {
             identifier,
@@ -45,6 +46,7 @@ impl Airport {
             geo_position,
             name,
             hide_tag,
+            location: statement.location().cloned(),
         })
     }
 }
@@ -58,8 +60,9 @@ pub struct Runway {
     pub opposite_elevation: f32,
     pub primary_course: f32,
     pub opposite_course: f32,
-    pub primary_position: (String, String),
-    pub opposite_position: (String, String),
+    pub primary_position: Position,
+    pub opposite_position: Position,
+    pub location: Option<Location>,
 }
 
 impl Runway {
@@ -87,8 +90,8 @@ impl Runway {
         let opposite_course = parts.next()
             .ok_or_else(|| anyhow!("missing opposite course"))?
             .parse::<f32>()?;
-        let primary_position = parse_string_position(&mut parts)?;
-        let opposite_position = parse_string_position(&mut parts)?;
+        let primary_position = parse_position(&mut parts)?;
+        let opposite_position = parse_position(&mut parts)?;
 
         Ok(Runway {
             airport,
@@ -100,6 +103,7 @@ impl Runway {
             opposite_course,
             primary_position,
             opposite_position,
+            location: statement.location().cloned(),
         })
     }
 
@@ -107,12 +111,8 @@ impl Runway {
         let primary_id = self.primary_number.to_string();
         let opposite_id = self.opposite_number.to_string();
 
-        let pt_a = sector.lookup_map_position(
-            &self.primary_position.0,
-            &self.primary_position.1)?;
-        let pt_b = sector.lookup_map_position(
-            &self.opposite_position.0,
-            &self.opposite_position.1)?;
+        let pt_a = sector.lookup_map_position(&self.primary_position)?;
+        let pt_b = sector.lookup_map_position(&self.opposite_position)?;
 
         let elevation_a = self.primary_elevation * FEET_TO_METRES;
         let elevation_b = self.opposite_elevation * FEET_TO_METRES;
@@ -134,7 +134,7 @@ impl Runway {
 pub struct Taxiway {
     pub airport: String,
     pub identifier: String,
-    pub geo_position: (String, String),
+    pub geo_position: Position,
 }
 
 impl Taxiway {
@@ -152,7 +152,7 @@ impl Taxiway {
         } else {
             "".to_owned()
         };
-        let geo_position = parse_string_position(&mut parts.into_iter())?;
+        let geo_position = parse_position(&mut parts.into_iter())?;
 
         Ok(Taxiway {
             airport,
@@ -162,9 +162,7 @@ impl Taxiway {
     }
 
     pub fn to_label(&self, sector: &Sector) -> anyhow::Result<domain::viewer::Label> {
-        let map_position = sector.lookup_map_position(
-            &self.geo_position.0,
-            &self.geo_position.1)?;
+        let map_position = sector.lookup_map_position(&self.geo_position)?;
         let map_aabb = (map_position.0, map_position.1, map_position.0, map_position.1);
 
         Ok(domain::viewer::Label {
@@ -181,7 +179,7 @@ impl Taxiway {
 pub struct Gate {
     pub airport: String,
     pub identifier: String,
-    pub geo_position: (String, String),
+    pub geo_position: Position,
     pub gate_type: Option<String>,
 }
 
@@ -195,7 +193,7 @@ impl Gate {
         let airport = parts.next()
             .ok_or_else(|| anyhow!("missing airport"))?
             .to_owned();
-        let geo_position = parse_string_position(&mut parts)?;
+        let geo_position = parse_position(&mut parts)?;
         let gate_type = parts.next()
             .map(String::from);
 
@@ -208,9 +206,7 @@ impl Gate {
     }
 
     pub fn to_label(&self, sector: &Sector) -> anyhow::Result<domain::viewer::Label> {
-        let map_position = sector.lookup_map_position(
-            &self.geo_position.0,
-            &self.geo_position.1)?;
+        let map_position = sector.lookup_map_position(&self.geo_position)?;
         let map_aabb = (map_position.0, map_position.1, map_position.0, map_position.1);
 
         Ok(domain::viewer::Label {
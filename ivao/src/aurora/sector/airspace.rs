@@ -5,21 +5,58 @@ use anyhow::anyhow;
 use open_air::domain;
 use open_air::domain::coords::calculate_aabb;
 
-use crate::aurora::gdf::Statement;
-use crate::aurora::sector::parsing::{parse_string_position, convert_geo_points};
+use crate::aurora::gdf::{Location, Statement};
+use crate::aurora::sector::parsing::{parse_position, convert_geo_points, Position};
 use crate::aurora::sector::Sector;
+use crate::aurora::sector::diagnostics::Collector;
+
+/// A single airspace or airway statement, which is either a point on its
+/// outline or a label.
+struct Part<'a> {
+    is_label: bool,
+    identifier: &'a str,
+    geo_position: Position,
+    font_size: Option<f32>,
+}
+
+impl<'a> Part<'a> {
+    fn parse(statement: &'a Statement, kind: &str) -> anyhow::Result<Part<'a>> {
+        let mut parts = statement.parts();
+
+        let is_label = match parts.next()
+            .ok_or_else(|| anyhow!("missing {} field type", kind))? {
+            "T" | "t" => false,
+            "L" | "l" => true,
+            value => return Err(anyhow!("unexpected {} field type: {}", kind, value)),
+        };
+        let identifier = parts.next()
+            .ok_or_else(|| anyhow!("missing {} identifier", kind))?;
+        let geo_position = parse_position(&mut parts)?;
+        let font_size = parts.next()
+            .and_then(|s| s.parse::<f32>().ok());
+
+        Ok(Part {
+            is_label,
+            identifier,
+            geo_position,
+            font_size,
+        })
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct AirspaceLabel {
-    pub geo_position: (String, String),
+    pub geo_position: Position,
     pub font_size: Option<f32>,
 }
 
 #[derive(Debug, Clone)]
 pub struct Airspace {
     pub identifier: String,
-    pub geo_points: Vec<(String, String)>,
+    pub geo_points: Vec<Position>,
     pub labels: Vec<AirspaceLabel>,
+    /// Where the first statement describing this airspace was read from.
+    pub location: Option<Location>,
 }
 
 impl Airspace {
@@ -28,9 +65,7 @@ impl Airspace {
         let aabb = calculate_aabb(points.iter().cloned());
         let labels = self.labels.iter()
             .map(|label| -> anyhow::Result<_> {
-                let map_position = sector.lookup_map_position(
-                    &label.geo_position.0,
-                    &label.geo_position.1)?;
+                let map_position = sector.lookup_map_position(&label.geo_position)?;
                 Ok(domain::AirspaceLabel {
                     map_position,
                     font_size: label.font_size.unwrap_or(4.0),
@@ -47,62 +82,61 @@ impl Airspace {
         })
     }
 
-    pub fn from_iterator(dest: &mut Vec<Airspace>, src: impl Iterator<Item=anyhow::Result<Statement>>) -> anyhow::Result<()> {
-        let mut airspaces = HashMap::new();
+    /// Parse the statements of an airspace section, grouping them by identifier
+    /// in the order each airspace first appears.
+    pub(crate) fn from_iterator(dest: &mut Vec<Airspace>, section: &str,
+                                src: impl Iterator<Item=anyhow::Result<Statement>>,
+                                diagnostics: &mut Collector) {
+        let mut indices = HashMap::new();
 
         for statement in src {
-            let statement = statement?;
-            let mut parts = statement.parts();
-
-            let is_label = match parts.next()
-                .ok_or_else(|| anyhow!("missing airspace field type: {}", statement.as_str()))? {
-                "T" | "t" => false,
-                "L" | "l" => true,
-                value => return Err(anyhow!("unexpected airspace field type: {}", value)),
+            let statement = match diagnostics.statement(section, statement) {
+                Some(x) => x,
+                None => continue,
+            };
+            let part = match Part::parse(&statement, "airspace") {
+                Ok(x) => x,
+                Err(err) => {
+                    diagnostics.invalid_statement(section, &statement, &err);
+                    continue;
+                }
             };
-            let identifier = parts.next()
-                .ok_or_else(|| anyhow!("missing airspace identifier"))?;
-            let geo_position = parse_string_position(&mut parts)?;
-            let font_size = parts.next()
-                .and_then(|s| s.parse::<f32>().ok());
-
-            if !airspaces.contains_key(identifier) {
-                let airspace = Airspace {
-                    identifier: identifier.to_string(),
+
+            let index = *indices.entry(part.identifier.to_owned()).or_insert_with(|| {
+                dest.push(Airspace {
+                    identifier: part.identifier.to_owned(),
                     geo_points: Vec::new(),
                     labels: Vec::new(),
-                };
-                airspaces.insert(identifier.to_string(), airspace);
-            }
-            let airspace = airspaces.get_mut(identifier).unwrap();
-
-            if is_label {
-                let label = AirspaceLabel {
-                    geo_position,
-                    font_size,
-                };
-                airspace.labels.push(label);
+                    location: statement.location().cloned(),
+                });
+                dest.len() - 1
+            });
+            let airspace = &mut dest[index];
+
+            if part.is_label {
+                airspace.labels.push(AirspaceLabel {
+                    geo_position: part.geo_position,
+                    font_size: part.font_size,
+                });
             } else {
-                airspace.geo_points.push(geo_position);
+                airspace.geo_points.push(part.geo_position);
             }
         }
-
-        dest.extend(airspaces.into_values());
-        Ok(())
     }
 }
 
-
 #[derive(Debug, Clone)]
 pub struct AirwayLabel {
-    pub geo_position: (String, String),
+    pub geo_position: Position,
 }
 
 #[derive(Debug, Clone)]
 pub struct Airway {
     pub identifier: String,
-    pub geo_points: Vec<(String, String)>,
+    pub geo_points: Vec<Position>,
     pub labels: Vec<AirwayLabel>,
+    /// Where the first statement describing this airway was read from.
+    pub location: Option<Location>,
 }
 
 impl Airway {
@@ -111,9 +145,7 @@ impl Airway {
         let aabb = calculate_aabb(points.iter().cloned());
         let labels = self.labels.iter()
             .map(|label| -> anyhow::Result<_> {
-                let map_position = sector.lookup_map_position(
-                    &label.geo_position.0,
-                    &label.geo_position.1)?;
+                let map_position = sector.lookup_map_position(&label.geo_position)?;
                 Ok(domain::AirwayLabel {
                     map_position,
                 })
@@ -130,44 +162,44 @@ impl Airway {
         })
     }
 
-    pub fn from_iterator(dest: &mut Vec<Airway>, src: impl Iterator<Item=anyhow::Result<Statement>>) -> anyhow::Result<()> {
-        let mut airways = HashMap::new();
+    /// Parse the statements of an airway section, grouping them by identifier
+    /// in the order each airway first appears.
+    pub(crate) fn from_iterator(dest: &mut Vec<Airway>, section: &str,
+                                src: impl Iterator<Item=anyhow::Result<Statement>>,
+                                diagnostics: &mut Collector) {
+        let mut indices = HashMap::new();
 
         for statement in src {
-            let statement = statement?;
-            let mut parts = statement.parts();
-
-            let is_label = match parts.next()
-                .ok_or_else(|| anyhow!("missing airway field type: {}", statement.as_str()))? {
-                "T" | "t" => false,
-                "L" | "l" => true,
-                value => return Err(anyhow!("unexpected airway field type: {}", value)),
+            let statement = match diagnostics.statement(section, statement) {
+                Some(x) => x,
+                None => continue,
+            };
+            let part = match Part::parse(&statement, "airway") {
+                Ok(x) => x,
+                Err(err) => {
+                    diagnostics.invalid_statement(section, &statement, &err);
+                    continue;
+                }
             };
-            let identifier = parts.next()
-                .ok_or_else(|| anyhow!("missing airway identifier"))?;
-            let geo_position = parse_string_position(&mut parts)?;
 
-            if !airways.contains_key(identifier) {
-                let airway = Airway {
-                    identifier: identifier.to_string(),
+            let index = *indices.entry(part.identifier.to_owned()).or_insert_with(|| {
+                dest.push(Airway {
+                    identifier: part.identifier.to_owned(),
                     geo_points: Vec::new(),
                     labels: Vec::new(),
-                };
-                airways.insert(identifier.to_string(), airway);
-            }
-            let airway = airways.get_mut(identifier).unwrap();
-
-            if is_label {
-                let label = AirwayLabel {
-                    geo_position,
-                };
-                airway.labels.push(label);
+                    location: statement.location().cloned(),
+                });
+                dest.len() - 1
+            });
+            let airway = &mut dest[index];
+
+            if part.is_label {
+                airway.labels.push(AirwayLabel {
+                    geo_position: part.geo_position,
+                });
             } else {
-                airway.geo_points.push(geo_position);
+                airway.geo_points.push(part.geo_position);
             }
         }
-
-        dest.extend(airways.into_values());
-        Ok(())
     }
 }
@@ -8,7 +8,7 @@ use log::warn;
 use open_air::domain::{AirspaceLayer, AirwayKind};
 use open_air::domain::viewer::{aabb_intersects, Colour, Label, normalise_aabb, SectionBuilder, Shape};
 
-use crate::aurora::sector::Sector;
+use crate::aurora::sector::{describe_location, Sector};
 
 struct PartialPolygon {
     shape: Shape,
@@ -149,8 +149,7 @@ impl PolygonBuilder {
 
     pub fn build(self) -> impl Iterator<Item=Shape> {
         self.shapes.into_iter()
-            .filter(Option::is_some)
-            .map(Option::unwrap)
+            .flatten()
             .map(PartialPolygon::into_shape)
     }
 }
@@ -183,6 +182,11 @@ fn simplify_shape(builder: &SectionBuilder, shape: &mut Shape, level: i16) {
 
 impl Sector {
     pub fn convert(&self, builder: &mut SectionBuilder) -> anyhow::Result<()> {
+        if builder.projection() != self.projection {
+            return Err(anyhow!("sector uses projection {:?}, but the output uses {:?}",
+                               self.projection, builder.projection()));
+        }
+
         for (name, colour) in self.defines.iter() {
             let value = match colour {
                 Colour::Value(value) => *value,
@@ -201,9 +205,9 @@ impl Sector {
                     ..Default::default()
                 };
 
-                for (lat, long) in fill.geo_points.iter() {
+                for position in fill.geo_points.iter() {
                     let pt = builder.truncate_2xf64(
-                        level, self.lookup_map_position(lat, long)?);
+                        level, self.lookup_map_position(position)?);
 
                     if shape.map_points.last() == Some(pt).as_ref() {
                         continue;
@@ -233,9 +237,9 @@ impl Sector {
 
             for geo in self.geo.iter() {
                 let start = builder.truncate_2xf64(
-                    level, self.lookup_map_position(&geo.start.0, &geo.start.1)?);
+                    level, self.lookup_map_position(&geo.start)?);
                 let end = builder.truncate_2xf64(
-                    level, self.lookup_map_position(&geo.end.0, &geo.end.1)?);
+                    level, self.lookup_map_position(&geo.end)?);
                 if start == end {
                     continue;
                 }
@@ -335,8 +339,7 @@ impl Sector {
                 continue;
             }
 
-            let map_position = self.lookup_map_position(
-                &airport.geo_position.0, &airport.geo_position.1)?;
+            let map_position = self.lookup_map_position(&airport.geo_position)?;
             let map_aabb = (map_position.0, map_position.1, map_position.0, map_position.1);
             let label = Label {
                 text: airport.identifier.clone(),
@@ -391,15 +394,15 @@ impl Sector {
             }
         }
 
-        let fixes = self.fixes.iter().map(|s| (&s.identifier, s.to_domain(self)))
-            .chain(self.ndbs.iter().map(|s| (&s.identifier, s.to_domain(self))))
-            .chain(self.vors.iter().map(|s| (&s.identifier, s.to_domain(self))))
-            .chain(self.vrps.iter().map(|s| (&s.identifier, s.to_domain(self))));
-        for (name, fix) in fixes {
+        let fixes = self.fixes.iter().map(|s| (&s.identifier, &s.location, s.to_domain(self)))
+            .chain(self.ndbs.iter().map(|s| (&s.identifier, &s.location, s.to_domain(self))))
+            .chain(self.vors.iter().map(|s| (&s.identifier, &s.location, s.to_domain(self))))
+            .chain(self.vrps.iter().map(|s| (&s.identifier, &s.location, s.to_domain(self))));
+        for (name, location, fix) in fixes {
             let domain = match fix {
                 Ok(v) => v,
                 Err(err) => {
-                    warn!("error converting fix {}: {}", name, err);
+                    warn!("{}error converting fix {}: {}", describe_location(location.as_ref()), name, err);
                     continue;
                 }
             };
@@ -417,16 +420,17 @@ impl Sector {
         }
 
         let airspaces = self.airspaces.iter()
-            .map(|a| (&a.identifier, a.to_domain(self, AirspaceLayer::Default)))
+            .map(|a| (a, a.to_domain(self, AirspaceLayer::Default)))
             .chain(self.airspaces_high.iter()
-                .map(|a| (&a.identifier, a.to_domain(self, AirspaceLayer::High))))
+                .map(|a| (a, a.to_domain(self, AirspaceLayer::High))))
             .chain(self.airspaces_low.iter()
-                .map(|a| (&a.identifier, a.to_domain(self, AirspaceLayer::Low))));
-        for (name, airspace) in airspaces {
-            let domain = match airspace {
+                .map(|a| (a, a.to_domain(self, AirspaceLayer::Low))));
+        for (airspace, domain) in airspaces {
+            let domain = match domain {
                 Ok(v) => v,
                 Err(err) => {
-                    warn!("error converting airspace {}: {}", name, err);
+                    warn!("{}error converting airspace {}: {}",
+                          describe_location(airspace.location.as_ref()), airspace.identifier, err);
                     continue;
                 }
             };
@@ -439,14 +443,15 @@ impl Sector {
         }
 
         let airways = self.airways_high.iter()
-            .map(|a| (&a.identifier, a.to_domain(self, AirwayKind::High)))
+            .map(|a| (a, a.to_domain(self, AirwayKind::High)))
             .chain(self.airways_low.iter()
-                .map(|a| (&a.identifier, a.to_domain(self, AirwayKind::Low))));
-        for (name, airway) in airways {
-            let domain = match airway {
+                .map(|a| (a, a.to_domain(self, AirwayKind::Low))));
+        for (airway, domain) in airways {
+            let domain = match domain {
                 Ok(v) => v,
                 Err(err) => {
-                    warn!("error converting airway {}: {}", name, err);
+                    warn!("{}error converting airway {}: {}",
+                          describe_location(airway.location.as_ref()), airway.identifier, err);
                     continue;
                 }
             };
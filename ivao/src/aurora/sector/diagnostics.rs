@@ -0,0 +1,158 @@
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+use serde::Serialize;
+
+use crate::aurora::gdf::{Location, Statement};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Severity {
+    /// Something looks wrong, but the sector is still usable.
+    Warning,
+    /// Something is missing or unusable, such as an include file or an unknown fix.
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A problem found while parsing or linting a sector.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// A short identifier for the kind of problem, such as `invalid-statement`.
+    pub code: &'static str,
+    pub location: Option<Location>,
+    /// The section the problem was found in.
+    pub section: String,
+    /// The offending statement, if known.
+    pub text: Option<String>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// Build a diagnostic from an error, taking the location from the statement
+    /// if there is one, or from the error otherwise.
+    pub fn from_error(severity: Severity, code: &'static str, section: &str, statement: Option<&Statement>, err: &anyhow::Error) -> Diagnostic {
+        let (location, message) = match (statement.and_then(|s| s.location()), err.downcast_ref::<Location>()) {
+            (Some(location), _) => (Some(location.clone()), format!("{:#}", err)),
+            (None, Some(location)) => {
+                // Skip the location itself, which is the outermost context.
+                let message = err.chain().skip(1)
+                    .map(|e| e.to_string())
+                    .collect::<Vec<_>>()
+                    .join(": ");
+                (Some(location.clone()), message)
+            }
+            (None, None) => (None, format!("{:#}", err)),
+        };
+
+        Diagnostic {
+            severity,
+            code,
+            location,
+            section: section.to_owned(),
+            text: statement.map(|s| s.as_str().to_owned()),
+            message,
+        }
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if let Some(location) = &self.location {
+            write!(f, "{}: ", location)?;
+        }
+        write!(f, "{}[{}]: ", self.severity, self.code)?;
+        if !self.section.is_empty() {
+            write!(f, "[{}] ", self.section)?;
+        }
+        write!(f, "{}", self.message)?;
+        if let Some(text) = &self.text {
+            write!(f, " (in {:?})", text)?;
+        }
+        Ok(())
+    }
+}
+
+/// The problems found in one sector file.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileReport {
+    pub file: String,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// The error returned when parsing in strict mode finds any problems.
+#[derive(Debug, Clone)]
+pub struct DiagnosticsError {
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Display for DiagnosticsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "found {} problem(s) in sector", self.diagnostics.len())?;
+        if let Some(first) = self.diagnostics.first() {
+            write!(f, ", first: {}", first)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for DiagnosticsError {}
+
+/// Collects diagnostics while parsing.
+#[derive(Debug, Default)]
+pub(crate) struct Collector {
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Collector {
+    pub fn report(&mut self, severity: Severity, code: &'static str, section: &str, statement: Option<&Statement>, err: &anyhow::Error) {
+        self.diagnostics.push(Diagnostic::from_error(severity, code, section, statement, err));
+    }
+
+    /// Unwrap a statement from an expanded section, recording the error if it
+    /// stands in for an include which couldn't be loaded.
+    pub fn statement(&mut self, section: &str, statement: anyhow::Result<Statement>) -> Option<Statement> {
+        match statement {
+            Ok(statement) => Some(statement),
+            Err(err) => {
+                self.report(Severity::Error, "include-error", section, None, &err);
+                None
+            }
+        }
+    }
+
+    /// Record a statement which couldn't be parsed.
+    pub fn invalid_statement(&mut self, section: &str, statement: &Statement, err: &anyhow::Error) {
+        self.report(Severity::Warning, "invalid-statement", section, Some(statement), err);
+    }
+
+    /// Parse each statement, recording anything which fails rather than stopping.
+    pub fn parse_statements<T>(&mut self, section: &str,
+                               statements: impl Iterator<Item=anyhow::Result<Statement>>,
+                               parse: impl Fn(&Statement) -> anyhow::Result<T>) -> Vec<T> {
+        let mut values = Vec::new();
+        for statement in statements {
+            let statement = match self.statement(section, statement) {
+                Some(x) => x,
+                None => continue,
+            };
+
+            match parse(&statement) {
+                Ok(value) => values.push(value),
+                Err(err) => self.invalid_statement(section, &statement, &err),
+            }
+        }
+        values
+    }
+}
@@ -0,0 +1,89 @@
+use anyhow::anyhow;
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_8};
+
+use crate::aurora::sector::FileSource;
+
+/// Decode the contents of a sector file into a string.
+///
+/// Files with a byte order mark, or which are valid UTF-8, are decoded as
+/// such. Anything else is decoded with `fallback` if given, or with whichever
+/// legacy encoding looks most likely otherwise. Many sector packages are saved
+/// as Windows-1252, which is what this usually turns out to be.
+pub fn decode(contents: &[u8], fallback: Option<&'static Encoding>) -> (String, &'static Encoding) {
+    if let Some((encoding, bom_length)) = Encoding::for_bom(contents) {
+        let (text, _) = encoding.decode_without_bom_handling(&contents[bom_length..]);
+        return (text.into_owned(), encoding);
+    }
+
+    if let Ok(text) = std::str::from_utf8(contents) {
+        return (text.to_owned(), UTF_8);
+    }
+
+    let encoding = fallback.unwrap_or_else(|| {
+        let mut detector = EncodingDetector::new();
+        detector.feed(contents, true);
+        detector.guess(None, false)
+    });
+    let (text, _) = encoding.decode_without_bom_handling(contents);
+    (text.into_owned(), encoding)
+}
+
+/// Look up an encoding by one of its WHATWG labels, such as `windows-1252`.
+pub fn encoding_for_label(label: &str) -> anyhow::Result<&'static Encoding> {
+    Encoding::for_label(label.as_bytes())
+        .ok_or_else(|| anyhow!("unknown encoding {}", label))
+}
+
+/// Wraps a [`FileSource`], re-encoding every file it reads as UTF-8.
+pub(crate) struct DecodingSource<'a, S> {
+    inner: &'a mut S,
+    fallback: Option<&'static Encoding>,
+}
+
+impl<'a, S: FileSource> DecodingSource<'a, S> {
+    pub fn new(inner: &'a mut S, fallback: Option<&'static Encoding>) -> DecodingSource<'a, S> {
+        DecodingSource {
+            inner,
+            fallback,
+        }
+    }
+}
+
+impl<'a, S: FileSource> FileSource for DecodingSource<'a, S> {
+    fn read_file(&mut self, path: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        Ok(self.inner.read_file(path)?.map(|contents| {
+            let (text, encoding) = decode(&contents, self.fallback);
+            if encoding != UTF_8 {
+                log::debug!("decoded {} as {}", path, encoding.name());
+            }
+            text.into_bytes()
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use encoding_rs::{UTF_16LE, WINDOWS_1252, WINDOWS_1250};
+
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode("Zürich".as_bytes(), None), ("Zürich".to_owned(), UTF_8));
+        assert_eq!(decode(b"\xFF\xFEZ\x00\xFC\x00", None), ("Zü".to_owned(), UTF_16LE));
+
+        let latin = b"LFPG;392;;N049.00.35.000;E002.32.52.000;Paris Charles de Gaulle;\n\
+            LFLY;659;;N045.43.38.000;E004.56.40.000;Lyon Bron A\xE9roport;\n";
+        let (text, encoding) = decode(latin, None);
+        assert_eq!(encoding, WINDOWS_1252);
+        assert!(text.contains("Lyon Bron Aéroport"));
+
+        let (text, encoding) = decode(b"\x9Alask\xE1", Some(WINDOWS_1250));
+        assert_eq!(encoding, WINDOWS_1250);
+        assert_eq!(text, "šlaská");
+
+        assert_eq!(encoding_for_label("latin1").unwrap(), WINDOWS_1252);
+        assert!(encoding_for_label("nonsense").is_err());
+    }
+}
@@ -4,9 +4,12 @@ use anyhow::anyhow;
 
 use open_air::domain;
 use open_air::domain::PointKind;
+use open_air::domain::coords::GeoPosition;
+use open_air::domain::frequency::Frequency;
+use open_air::domain::navaid::{dme_channel, Navaid, NavaidKind};
 
-use crate::aurora::gdf::Statement;
-use crate::aurora::sector::parsing::{frequency_to_int, parse_string_position};
+use crate::aurora::gdf::{Location, Statement};
+use crate::aurora::sector::parsing::parse_geo_position;
 use crate::aurora::sector::Sector;
 
 #[derive(Debug, Clone, Copy)]
@@ -45,9 +48,10 @@ impl TryFrom<i32> for FixType {
 #[derive(Debug, Clone)]
 pub struct Fix {
     pub identifier: String,
-    pub geo_position: (String, String),
+    pub geo_position: GeoPosition,
     pub fix_type: FixType,
     pub boundary: bool,
+    pub location: Option<Location>,
 }
 
 impl Fix {
@@ -57,7 +61,7 @@ impl Fix {
         let identifier = parts.next()
             .ok_or_else(|| anyhow!("missing identifier"))?
             .to_owned();
-        let geo_position = parse_string_position(&mut parts)?;
+        let geo_position = parse_geo_position(&mut parts)?;
         let fix_type = match parts.next() {
             Some(value) => value.parse::<i32>()?.try_into()?,
             None => FixType::Hidden,
@@ -69,13 +73,12 @@ impl Fix {
             geo_position,
             fix_type,
             boundary,
+            location: statement.location().cloned(),
         })
     }
 
     pub fn to_domain(&self, sector: &Sector) -> anyhow::Result<domain::Point> {
-        let position = sector.lookup_map_position(
-            &self.geo_position.0,
-            &self.geo_position.1)?;
+        let position = sector.projection.project(self.geo_position);
         Ok(domain::Point {
             kind: PointKind::FIX {
                 kind: self.fix_type.into(),
@@ -87,11 +90,13 @@ impl Fix {
     }
 }
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone)]
 pub struct NDB {
     pub identifier: String,
     pub frequency: String,
-    pub geo_position: (String, String),
+    pub geo_position: GeoPosition,
+    pub location: Option<Location>,
 }
 
 impl NDB {
@@ -104,23 +109,24 @@ impl NDB {
         let frequency = parts.next()
             .ok_or_else(|| anyhow!("missing frequency"))?
             .to_owned();
-        let geo_position = parse_string_position(&mut parts)?;
+        let geo_position = parse_geo_position(&mut parts)?;
 
         Ok(NDB {
             identifier,
             frequency,
             geo_position,
+            location: statement.location().cloned(),
         })
     }
 
     pub fn to_domain(&self, sector: &Sector) -> anyhow::Result<domain::Point> {
-        let position = sector.lookup_map_position(
-            &self.geo_position.0,
-            &self.geo_position.1)?;
+        let position = sector.projection.project(self.geo_position);
+        let frequency = self.frequency.parse::<Frequency>()?;
+        if !matches!(frequency, Frequency::LowFrequency(_)) {
+            return Err(anyhow!("not an NDB frequency: {}", frequency));
+        }
         Ok(domain::Point {
-            kind: PointKind::NDB {
-                frequency: frequency_to_int(&self.frequency)?,
-            },
+            kind: PointKind::NDB(Navaid::new(NavaidKind::Ndb, frequency)),
             name: self.identifier.clone(),
             map_position: position,
         })
@@ -128,11 +134,13 @@ impl NDB {
 }
 
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone)]
 pub struct VOR {
     pub identifier: String,
     pub frequency: String,
-    pub geo_position: (String, String),
+    pub geo_position: GeoPosition,
+    pub location: Option<Location>,
 }
 
 impl VOR {
@@ -145,23 +153,29 @@ impl VOR {
         let frequency = parts.next()
             .ok_or_else(|| anyhow!("missing frequency"))?
             .to_owned();
-        let geo_position = parse_string_position(&mut parts)?;
+        let geo_position = parse_geo_position(&mut parts)?;
 
         Ok(VOR {
             identifier,
             frequency,
             geo_position,
+            location: statement.location().cloned(),
         })
     }
 
     pub fn to_domain(&self, sector: &Sector) -> anyhow::Result<domain::Point> {
-        let position = sector.lookup_map_position(
-            &self.geo_position.0,
-            &self.geo_position.1)?;
+        let position = sector.projection.project(self.geo_position);
+        let frequency = self.frequency.parse::<Frequency>()?;
+        if !matches!(frequency, Frequency::Nav(_)) {
+            return Err(anyhow!("not a VOR frequency: {}", frequency));
+        }
+        // Sector files don't say what a VOR is aligned to, so assume the
+        // variation at the station.
+        let mut navaid = Navaid::new(NavaidKind::Vor, frequency);
+        navaid.dme_channel = dme_channel(frequency);
+        navaid.declination = Some(sector.magnetic_model.variation(self.geo_position));
         Ok(domain::Point {
-            kind: PointKind::VOR {
-                frequency: frequency_to_int(&self.frequency)?,
-            },
+            kind: PointKind::VOR(navaid),
             name: self.identifier.clone(),
             map_position: position,
         })
@@ -169,11 +183,13 @@ impl VOR {
 }
 
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone)]
 pub struct VRP {
     pub identifier: String,
     pub altitude: Option<(f32, f32)>,
-    pub geo_position: (String, String),
+    pub geo_position: GeoPosition,
+    pub location: Option<Location>,
 }
 
 impl VRP {
@@ -199,22 +215,21 @@ impl VRP {
             .to_owned();
         let altitude = parts.next()
             .filter(|s| !s.is_empty())
-            .map(|part| VRP::parse_range(part))
+            .map(VRP::parse_range)
             .transpose()
             .map_err(|e| anyhow!("failed to parse VRP range {}: {}", statement.as_str(), e))?;
-        let geo_position = parse_string_position(&mut parts)?;
+        let geo_position = parse_geo_position(&mut parts)?;
 
         Ok(VRP {
             identifier,
             altitude,
             geo_position,
+            location: statement.location().cloned(),
         })
     }
 
     pub fn to_domain(&self, sector: &Sector) -> anyhow::Result<domain::Point> {
-        let position = sector.lookup_map_position(
-            &self.geo_position.0,
-            &self.geo_position.1)?;
+        let position = sector.projection.project(self.geo_position);
         Ok(domain::Point {
             kind: PointKind::VRP {
                 altitude: self.altitude,
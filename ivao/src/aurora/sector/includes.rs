@@ -0,0 +1,187 @@
+use std::collections::{HashMap, VecDeque};
+
+use anyhow::anyhow;
+
+use crate::aurora::gdf::{File, Statement};
+use crate::aurora::sector::{load_file_contents, parse_file, FileSource};
+
+/// How deeply `F;` includes may be nested before giving up.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// The statements of every section in a sector, with `F;` includes expanded.
+///
+/// Included files may include others in turn, and may contain named sections
+/// of their own, whose statements are added to the end of the section of the
+/// same name.
+/// Includes which can't be loaded are kept in place as errors, so they are
+/// reported alongside the statements around them.
+pub(crate) struct ExpandedSections {
+    sections: HashMap<String, Vec<anyhow::Result<Statement>>>,
+}
+
+struct Expander<'a, S> {
+    fs: &'a mut S,
+    include_dirs: &'a [String],
+    /// The files currently being expanded, outermost first.
+    stack: Vec<String>,
+    /// Named sections found in includes, with the stack they were found at,
+    /// to be added once the main file's own statements have been.
+    pending: VecDeque<(String, Vec<Statement>, Vec<String>)>,
+    sections: HashMap<String, Vec<anyhow::Result<Statement>>>,
+}
+
+impl<'a, S: FileSource> Expander<'a, S> {
+    fn expand(&mut self, section: &str, statements: &[Statement]) {
+        for statement in statements {
+            let mut parts = statement.parts();
+            let result = if parts.next() == Some("F") {
+                match statement.parse_with(|_| self.include(section, parts.next())) {
+                    Ok(()) => continue,
+                    Err(err) => Err(err),
+                }
+            } else {
+                Ok(statement.clone())
+            };
+
+            self.sections.entry(section.to_owned())
+                .or_default()
+                .push(result);
+        }
+    }
+
+    fn include(&mut self, section: &str, name: Option<&str>) -> anyhow::Result<()> {
+        let name = name.ok_or_else(|| anyhow!("missing filename"))?;
+        let (path, contents) = load_file_contents(self.fs, self.include_dirs, name)?
+            .ok_or_else(|| anyhow!("missing referenced file: {}", name))?;
+
+        if self.stack.iter().any(|p| p.eq_ignore_ascii_case(path.as_str())) {
+            let chain = self.stack.iter()
+                .map(String::as_str)
+                .chain(std::iter::once(path.as_str()))
+                .collect::<Vec<_>>()
+                .join(" -> ");
+            return Err(anyhow!("include cycle: {}", chain));
+        }
+        if self.stack.len() > MAX_INCLUDE_DEPTH {
+            return Err(anyhow!("includes nested more than {} deep at {}", MAX_INCLUDE_DEPTH, name));
+        }
+
+        let file = parse_file(path.as_str(), contents)?;
+        self.stack.push(path.as_str().to_owned());
+        for included in file.sections() {
+            if included.name().is_empty() {
+                self.expand(section, included.statements());
+            } else {
+                self.pending.push_back((
+                    included.name().to_owned(),
+                    included.statements().to_vec(),
+                    self.stack.clone()));
+            }
+        }
+        self.stack.pop();
+
+        Ok(())
+    }
+}
+
+impl ExpandedSections {
+    /// Expand the includes in every section of a sector's main file.
+    pub fn expand(fs: &mut impl FileSource, include_dirs: &[String], root_name: &str, root: &File) -> ExpandedSections {
+        let mut expander = Expander {
+            fs,
+            include_dirs,
+            stack: vec![root_name.to_owned()],
+            pending: VecDeque::new(),
+            sections: HashMap::new(),
+        };
+
+        for section in root.sections() {
+            expander.expand(section.name(), section.statements());
+        }
+
+        while let Some((section, statements, stack)) = expander.pending.pop_front() {
+            expander.stack = stack;
+            expander.expand(&section, &statements);
+        }
+
+        ExpandedSections {
+            sections: expander.sections,
+        }
+    }
+
+    /// Take the statements of a section, leaving it empty.
+    pub fn take(&mut self, name: &str) -> impl Iterator<Item=anyhow::Result<Statement>> {
+        self.sections.remove(name)
+            .unwrap_or_default()
+            .into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn expand(fs: &mut HashMap<String, Vec<u8>>, root: &str) -> ExpandedSections {
+        let root = File::parse_named(root, "Sector.isc").unwrap();
+        ExpandedSections::expand(fs, &[], "Sector.isc", &root)
+    }
+
+    fn contents(sections: &mut ExpandedSections, name: &str) -> Vec<String> {
+        sections.take(name)
+            .map(|s| match s {
+                Ok(s) => s.as_str().to_owned(),
+                Err(err) => format!("{:#}", err),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_nested_includes() {
+        let mut fs: HashMap<String, Vec<u8>> = HashMap::new();
+        fs.insert("Include/a.fix".into(), "A\nF;b.fix\nA2\n".into());
+        fs.insert("Include/b.fix".into(), "B\n[VOR]\nV\nF;c.fix\n".into());
+        fs.insert("Include/c.fix".into(), "C\nF;b.fix\n".into());
+
+        let mut sections = expand(&mut fs, "[FIXES]\nROOT\nF;a.fix\n[VOR]\nROOTV\n");
+        assert_eq!(contents(&mut sections, "FIXES"), vec!["ROOT", "A", "B", "A2"]);
+        assert_eq!(contents(&mut sections, "VOR"), vec![
+            "ROOTV",
+            "V",
+            "C",
+            "Include/c.fix:2: include cycle: Sector.isc -> Include/a.fix -> Include/b.fix -> Include/c.fix -> Include/b.fix",
+        ]);
+        assert!(contents(&mut sections, "VOR").is_empty());
+    }
+
+    #[test]
+    fn test_include_cycle() {
+        let mut fs: HashMap<String, Vec<u8>> = HashMap::new();
+        fs.insert("Include/a.fix".into(), "A\nF;b.fix\n".into());
+        fs.insert("Include/b.fix".into(), "B\nF;a.fix\n".into());
+        fs.insert("Include/self.fix".into(), "F;self.fix\n".into());
+
+        let mut sections = expand(&mut fs, "[FIXES]\nF;a.fix\nF;self.fix\nF;missing.fix\n");
+        assert_eq!(contents(&mut sections, "FIXES"), vec![
+            "A",
+            "B",
+            "Include/b.fix:2: include cycle: Sector.isc -> Include/a.fix -> Include/b.fix -> Include/a.fix",
+            "Include/self.fix:1: include cycle: Sector.isc -> Include/self.fix -> Include/self.fix",
+            "Sector.isc:4: missing referenced file: missing.fix",
+        ]);
+    }
+
+    #[test]
+    fn test_include_depth() {
+        let mut fs: HashMap<String, Vec<u8>> = HashMap::new();
+        for i in 0..32 {
+            fs.insert(format!("Include/{}.fix", i), format!("F;{}.fix\n", i + 1).into());
+        }
+
+        let mut sections = expand(&mut fs, "[FIXES]\nF;0.fix\n");
+        let statements = contents(&mut sections, "FIXES");
+        assert_eq!(statements.len(), 1);
+        assert!(statements[0].ends_with("includes nested more than 16 deep at 16.fix"), "{}", statements[0]);
+    }
+}
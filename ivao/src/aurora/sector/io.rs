@@ -1,14 +1,32 @@
 use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek};
 use std::path::PathBuf;
 
 use anyhow::anyhow;
 use relative_path::RelativePath;
 use walkdir::WalkDir;
+use zip::ZipArchive;
 
 pub trait FileSource {
     fn read_file(&mut self, path: &str) -> anyhow::Result<Option<Vec<u8>>>;
 }
 
+impl<S: FileSource + ?Sized> FileSource for Box<S> {
+    fn read_file(&mut self, path: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        (**self).read_file(path)
+    }
+}
+
+/// Open a sector package, which may be either a directory or a zip file.
+pub fn open_package(path: PathBuf) -> anyhow::Result<Box<dyn FileSource>> {
+    if path.is_dir() {
+        Ok(Box::new(DirectorySource::new(path)?))
+    } else {
+        Ok(Box::new(ZipSource::open(path)?))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct DirectorySource {
     paths: HashMap<String, PathBuf>,
@@ -54,8 +72,158 @@ impl FileSource for DirectorySource {
     }
 }
 
+/// Reads files straight out of a zip archive.
+#[derive(Debug)]
+pub struct ZipSource<R> {
+    archive: ZipArchive<R>,
+    paths: HashMap<String, usize>,
+}
+
+impl ZipSource<File> {
+    pub fn open(path: PathBuf) -> anyhow::Result<ZipSource<File>> {
+        let file = File::open(&path)
+            .map_err(|err| anyhow!("failed to open {}: {}", path.display(), err))?;
+        ZipSource::new(file)
+    }
+}
+
+impl<R: Read + Seek> ZipSource<R> {
+    pub fn new(reader: R) -> anyhow::Result<ZipSource<R>> {
+        let mut archive = ZipArchive::new(reader)?;
+        let mut paths = HashMap::new();
+
+        for index in 0..archive.len() {
+            let entry = archive.by_index_raw(index)?;
+            if !entry.is_file() {
+                continue;
+            }
+
+            // Archives made on Windows sometimes use backslashes.
+            let path = RelativePath::new(&entry.name().replace('\\', "/")).normalize();
+            paths.insert(path.as_str().to_lowercase(), index);
+        }
+
+        Ok(ZipSource {
+            archive,
+            paths,
+        })
+    }
+}
+
+impl<R: Read + Seek> FileSource for ZipSource<R> {
+    fn read_file(&mut self, path: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        let path = path.to_lowercase();
+
+        if let Some(index) = self.paths.get(&path) {
+            let mut entry = self.archive.by_index(*index)?;
+            let mut contents = Vec::with_capacity(entry.size() as usize);
+            entry.read_to_end(&mut contents)?;
+            Ok(Some(contents))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// Stacks several sources, reading each file from the first layer which has it.
+///
+/// This lets a small set of local patches sit on top of an upstream package.
+#[derive(Default)]
+pub struct OverlaySource {
+    layers: Vec<(String, Box<dyn FileSource>)>,
+    origins: HashMap<String, usize>,
+}
+
+impl OverlaySource {
+    pub fn new() -> OverlaySource {
+        Default::default()
+    }
+
+    /// Add a layer underneath all of the existing ones.
+    pub fn add_layer(&mut self, name: &str, source: Box<dyn FileSource>) {
+        self.layers.push((name.to_owned(), source));
+    }
+
+    /// The name of the layer a file was read from, if it has been read.
+    pub fn origin(&self, path: &str) -> Option<&str> {
+        self.origins.get(path)
+            .map(|index| self.layers[*index].0.as_str())
+    }
+
+    /// Every file read so far, with the name of the layer it came from.
+    pub fn origins(&self) -> impl Iterator<Item=(&str, &str)> {
+        self.origins.iter()
+            .map(move |(path, index)| (path.as_str(), self.layers[*index].0.as_str()))
+    }
+}
+
+impl FileSource for OverlaySource {
+    fn read_file(&mut self, path: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        for (index, (name, layer)) in self.layers.iter_mut().enumerate() {
+            if let Some(contents) = layer.read_file(path)? {
+                log::debug!("read {} from {}", path, name);
+                self.origins.insert(path.to_owned(), index);
+                return Ok(Some(contents));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
 impl FileSource for HashMap<String, Vec<u8>> {
     fn read_file(&mut self, path: &str) -> anyhow::Result<Option<Vec<u8>>> {
         Ok(self.get(path).cloned())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use zip::write::FileOptions;
+    use zip::{CompressionMethod, ZipWriter};
+
+    use super::*;
+
+    #[test]
+    fn test_zip_source() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+        writer.add_directory("Include/", options).unwrap();
+        writer.start_file("Sector.isc", options).unwrap();
+        writer.write_all(b"[INFO]\n").unwrap();
+        writer.start_file("Include\\EGTT\\Fixes.fix", options).unwrap();
+        writer.write_all(b"ABC;N051.00.00.000;W001.00.00.000;\n").unwrap();
+        let contents = writer.finish().unwrap();
+
+        let mut source = ZipSource::new(contents).unwrap();
+        assert_eq!(source.read_file("sector.isc").unwrap().unwrap(), b"[INFO]\n");
+        assert_eq!(source.read_file("Include/egtt/FIXES.fix").unwrap().unwrap(),
+                   b"ABC;N051.00.00.000;W001.00.00.000;\n");
+        assert_eq!(source.read_file("Include").unwrap(), None);
+        assert_eq!(source.read_file("missing.fix").unwrap(), None);
+    }
+
+    #[test]
+    fn test_overlay_source() {
+        let mut patch: HashMap<String, Vec<u8>> = HashMap::new();
+        patch.insert("Include/fixes.fix".into(), b"patched".to_vec());
+        let mut upstream: HashMap<String, Vec<u8>> = HashMap::new();
+        upstream.insert("Sector.isc".into(), b"sector".to_vec());
+        upstream.insert("Include/fixes.fix".into(), b"upstream".to_vec());
+
+        let mut source = OverlaySource::new();
+        source.add_layer("patch", Box::new(patch));
+        source.add_layer("upstream", Box::new(upstream));
+
+        assert_eq!(source.read_file("Include/fixes.fix").unwrap().unwrap(), b"patched");
+        assert_eq!(source.read_file("Sector.isc").unwrap().unwrap(), b"sector");
+        assert_eq!(source.read_file("missing.fix").unwrap(), None);
+
+        assert_eq!(source.origin("Include/fixes.fix"), Some("patch"));
+        assert_eq!(source.origin("Sector.isc"), Some("upstream"));
+        assert_eq!(source.origin("missing.fix"), None);
+        assert_eq!(source.origins().count(), 2);
+    }
+}
@@ -0,0 +1,273 @@
+use std::collections::HashMap;
+
+use open_air::domain::coords::initial_bearing;
+use open_air::domain::magnetic::MagneticModel;
+use open_air::domain::viewer::Colour;
+
+use crate::aurora::gdf::Location;
+use crate::aurora::sector::{Diagnostic, FileSource, ParseOptions, Sector, Severity};
+use crate::aurora::sector::parsing::Position;
+
+/// How far a runway's published course may be from its geometry, in degrees.
+const RUNWAY_COURSE_TOLERANCE: f64 = 10.;
+
+fn diagnostic(severity: Severity, code: &'static str, section: &str, location: &Option<Location>, message: String) -> Diagnostic {
+    Diagnostic {
+        severity,
+        code,
+        location: location.clone(),
+        section: section.to_owned(),
+        text: None,
+        message,
+    }
+}
+
+/// The smallest angle between two bearings, in degrees.
+fn angle_between(a: f64, b: f64) -> f64 {
+    let diff = (a - b).rem_euclid(360.);
+    diff.min(360. - diff)
+}
+
+/// Checks a parsed sector for problems which don't stop it being parsed.
+struct Linter<'a> {
+    sector: &'a Sector,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Linter<'a> {
+    fn check_position(&mut self, section: &str, location: &Option<Location>, position: &Position) {
+        if let Err(err) = self.sector.lookup_geo_position(position) {
+            let message = match position {
+                Position::Reference { latitude, longitude } if latitude == longitude => format!("unknown fix {}", latitude),
+                _ => format!("unresolved position {}: {}", position, err),
+            };
+            self.diagnostics.push(diagnostic(Severity::Error, "unresolved-fix", section, location, message));
+        }
+    }
+
+    fn check_colour(&mut self, section: &str, location: &Option<Location>, colour: &Colour) {
+        if let Colour::Reference(name) = colour {
+            if !self.sector.defines.contains_key(name) {
+                let message = format!("colour {} is not in [DEFINE]", name);
+                self.diagnostics.push(diagnostic(Severity::Error, "undefined-colour", section, location, message));
+            }
+        }
+    }
+
+    fn check_duplicates<'b>(&mut self, section: &str, items: impl Iterator<Item=(&'b String, &'b Option<Location>)>) {
+        let mut seen: HashMap<&str, &Option<Location>> = HashMap::new();
+        for (identifier, location) in items {
+            if let Some(first) = seen.get(identifier.as_str()) {
+                let message = match first {
+                    Some(first) => format!("duplicate identifier {}, first defined at {}", identifier, first),
+                    None => format!("duplicate identifier {}", identifier),
+                };
+                self.diagnostics.push(diagnostic(Severity::Warning, "duplicate-identifier", section, location, message));
+            } else {
+                seen.insert(identifier, location);
+            }
+        }
+    }
+
+    fn check_geo(&mut self) {
+        for geo in self.sector.geo.iter() {
+            self.check_position("GEO", &geo.location, &geo.start);
+            self.check_position("GEO", &geo.location, &geo.end);
+            if let Some(colour) = &geo.color {
+                self.check_colour("GEO", &geo.location, colour);
+            }
+        }
+    }
+
+    fn check_fills(&mut self) {
+        for fill in self.sector.fill_colors.iter() {
+            for point in fill.geo_points.iter() {
+                self.check_position("FILLCOLOR", &fill.location, point);
+            }
+            self.check_colour("FILLCOLOR", &fill.location, &fill.fill_color);
+            self.check_colour("FILLCOLOR", &fill.location, &fill.stroke_color);
+        }
+    }
+
+    fn check_airways(&mut self) {
+        let airways = self.sector.airways_high.iter().map(|a| ("HIGH AIRWAY", a))
+            .chain(self.sector.airways_low.iter().map(|a| ("LOW AIRWAY", a)));
+        for (section, airway) in airways {
+            let positions = airway.geo_points.iter()
+                .chain(airway.labels.iter().map(|l| &l.geo_position));
+            for position in positions {
+                self.check_position(section, &airway.location, position);
+            }
+        }
+    }
+
+    fn check_airspaces(&mut self) {
+        let airspaces = self.sector.airspaces.iter().map(|a| ("AIRSPACE", a))
+            .chain(self.sector.airspaces_high.iter().map(|a| ("AIRSPACE_HIGH", a)))
+            .chain(self.sector.airspaces_low.iter().map(|a| ("AIRSPACE_LOW", a)));
+        for (section, airspace) in airspaces {
+            if airspace.geo_points.len() < 3 {
+                let message = format!("airspace {} has {} point(s), but needs at least 3",
+                                      airspace.identifier, airspace.geo_points.len());
+                self.diagnostics.push(diagnostic(Severity::Warning, "degenerate-airspace", section, &airspace.location, message));
+            }
+
+            let positions = airspace.geo_points.iter()
+                .chain(airspace.labels.iter().map(|l| &l.geo_position));
+            for position in positions {
+                self.check_position(section, &airspace.location, position);
+            }
+        }
+    }
+
+    fn check_runways(&mut self) {
+        let model = &self.sector.magnetic_model;
+        for runway in self.sector.runways.iter() {
+            let ends = self.sector.lookup_geo_position(&runway.primary_position)
+                .and_then(|a| Ok((a, self.sector.lookup_geo_position(&runway.opposite_position)?)));
+            let (a, b) = match ends {
+                Ok(x) => x,
+                Err(err) => {
+                    let message = format!("runway {}/{} at {} has an unresolved end: {}",
+                                          runway.primary_number, runway.opposite_number, runway.airport, err);
+                    self.diagnostics.push(diagnostic(Severity::Error, "unresolved-fix", "RUNWAY", &runway.location, message));
+                    continue;
+                }
+            };
+
+            // Runway courses are magnetic.
+            let bearing = model.true_to_magnetic(a, initial_bearing(a.into(), b.into()));
+            let course = runway.primary_course as f64;
+            if angle_between(course, bearing) > RUNWAY_COURSE_TOLERANCE {
+                let message = format!("runway {} at {} has course {:.0}, but its ends are on a magnetic bearing of {:.0}",
+                                      runway.primary_number, runway.airport, course, bearing);
+                self.diagnostics.push(diagnostic(Severity::Warning, "runway-course", "RUNWAY", &runway.location, message));
+            }
+        }
+    }
+
+    fn check_identifiers(&mut self) {
+        let sector = self.sector;
+        self.check_duplicates("AIRPORT", sector.airports.iter().map(|x| (&x.identifier, &x.location)));
+        self.check_duplicates("FIXES", sector.fixes.iter().map(|x| (&x.identifier, &x.location)));
+        self.check_duplicates("NDB", sector.ndbs.iter().map(|x| (&x.identifier, &x.location)));
+        self.check_duplicates("VOR", sector.vors.iter().map(|x| (&x.identifier, &x.location)));
+    }
+}
+
+/// Check a parsed sector for problems which would make it convert badly.
+pub fn lint_sector(sector: &Sector) -> Vec<Diagnostic> {
+    let mut linter = Linter {
+        sector,
+        diagnostics: Vec::new(),
+    };
+
+    linter.check_identifiers();
+    linter.check_runways();
+    linter.check_geo();
+    linter.check_fills();
+    linter.check_airways();
+    linter.check_airspaces();
+    linter.diagnostics
+}
+
+/// Parse a sector and check it for problems, returning everything found.
+///
+/// The magnetic model, if given, replaces the sector's own for checking runways.
+/// A sector which can't be parsed at all is reported as a single `parse-error`.
+pub fn lint(fs: &mut impl FileSource, name: &str, options: ParseOptions, magnetic_model: Option<&MagneticModel>) -> Vec<Diagnostic> {
+    let options = ParseOptions {
+        strict: false,
+        ..options
+    };
+    let (mut sector, mut diagnostics) = match Sector::parse_with_diagnostics(fs, name, options) {
+        Ok(x) => x,
+        Err(err) => return vec![Diagnostic::from_error(Severity::Error, "parse-error", "", None, &err)],
+    };
+    if let Some(model) = magnetic_model {
+        sector.magnetic_model = model.clone();
+    }
+
+    diagnostics.extend(lint_sector(&sector));
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    const SECTOR: &str = "
+        [INFO]
+        N051.00.00.000
+        W001.00.00.000
+        60
+        40
+        0
+
+        [DEFINE]
+        COAST;#00FF00
+
+        [FIXES]
+        ABC;N051.00.00.000;W001.00.00.000;
+        ABC;N051.10.00.000;W001.00.00.000;
+
+        [RUNWAY]
+        EGLL;09L;27R;83;78;90;270;N051.28.39.000;W000.29.06.000;N051.28.39.000;W000.25.58.000;
+        EGLL;09R;27L;75;77;180;0;N051.27.53.000;W000.28.57.000;N051.27.53.000;W000.26.01.000;
+
+        [GEO]
+        ABC;ABC;N051.00.00.000;W000.00.00.000;COAST
+        ABC;ABC;XYZ;XYZ;SEA
+
+        [AIRSPACE]
+        T;LON;N051.00.00.000;W001.00.00.000
+        T;LON;N052.00.00.000;W001.00.00.000
+    ";
+
+    #[test]
+    fn test_lint() {
+        let mut fs: HashMap<String, Vec<u8>> = HashMap::new();
+        fs.insert("Sector.isc".into(), SECTOR.into());
+
+        let diagnostics = lint(&mut fs, "Sector.isc", ParseOptions::default(), None);
+        let codes = diagnostics.iter()
+            .map(|d| (d.code, d.section.as_str(), d.location.as_ref().map(|l| l.line)))
+            .collect::<Vec<_>>();
+        assert_eq!(codes, vec![
+            ("duplicate-identifier", "FIXES", Some(14)),
+            ("runway-course", "RUNWAY", Some(18)),
+            ("unresolved-fix", "GEO", Some(22)),
+            ("undefined-colour", "GEO", Some(22)),
+            ("degenerate-airspace", "AIRSPACE", Some(25)),
+        ]);
+        assert_eq!(diagnostics[2].message, "unknown fix XYZ");
+
+        // The given model replaces the sector's own, and this one accounts for
+        // 09R's course but not 09L's.
+        let diagnostics = lint(&mut fs, "Sector.isc", ParseOptions::default(), Some(&MagneticModel::Fixed(-90.)));
+        let runways = diagnostics.iter()
+            .filter(|d| d.code == "runway-course")
+            .map(|d| d.location.as_ref().map(|l| l.line))
+            .collect::<Vec<_>>();
+        assert_eq!(runways, vec![Some(17)]);
+
+        // 15° east puts 09L's ends on a magnetic bearing of 075.
+        let diagnostics = lint(&mut fs, "Sector.isc", ParseOptions::default(), Some(&MagneticModel::Fixed(15.)));
+        let runway = diagnostics.iter().find(|d| d.code == "runway-course" && d.location.as_ref().unwrap().line == 17);
+        assert_eq!(runway.unwrap().message, "runway 09L at EGLL has course 90, but its ends are on a magnetic bearing of 75");
+
+        let diagnostics = lint(&mut fs, "Missing.isc", ParseOptions::default(), None);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, "parse-error");
+        assert_eq!(diagnostics[0].to_string(), "error[parse-error]: missing section main file");
+    }
+
+    #[test]
+    fn test_angle_between() {
+        assert_eq!(angle_between(10., 350.), 20.);
+        assert_eq!(angle_between(270., 90.), 180.);
+        assert_eq!(angle_between(0., 360.), 0.);
+    }
+}
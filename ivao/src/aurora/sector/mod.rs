@@ -1,24 +1,36 @@
-use std::collections::{HashMap, VecDeque};
-use std::iter::FromIterator;
+use std::collections::HashMap;
 
 use anyhow::anyhow;
 use log::warn;
 use relative_path::{RelativePath, RelativePathBuf};
 
 use airport::Airport;
-pub use io::{DirectorySource, FileSource};
-use open_air::domain::coords::geo_to_map;
+use diagnostics::Collector;
+use encoding::DecodingSource;
+use encoding_rs::Encoding;
+use includes::ExpandedSections;
+use parsing::Position;
+pub use diagnostics::{Diagnostic, DiagnosticsError, FileReport, Severity};
+pub use encoding::{decode, encoding_for_label};
+pub use io::{open_package, DirectorySource, FileSource, OverlaySource, ZipSource};
+pub use lint::{lint, lint_sector};
+use open_air::domain::coords::{GeoPosition, Projection};
+use open_air::domain::magnetic::MagneticModel;
 use open_air::domain::viewer::Colour;
 use visual::Geo;
 
-use crate::aurora::gdf::{parse_colour, Statement};
-use crate::aurora::gdf::{File, parse_latitude, parse_longitude, Section};
+use crate::aurora::gdf::parse_colour;
+use crate::aurora::gdf::{File, Location, parse_latitude, parse_longitude, Section, Statement};
 use crate::aurora::sector::airport::{Gate, Runway, Taxiway};
 use crate::aurora::sector::airspace::{Airspace, Airway};
 use crate::aurora::sector::fixes::{Fix, NDB, VOR, VRP};
 use crate::aurora::sector::visual::FillColor;
 
+mod diagnostics;
+mod encoding;
+mod includes;
 mod io;
+mod lint;
 mod parsing;
 mod visual;
 mod convert;
@@ -28,125 +40,77 @@ mod fixes;
 
 const INCLUDE_PATH: &str = "Include";
 
-fn load_file_contents(fs: &mut impl FileSource, include_dirs: &[String], name: &str) -> anyhow::Result<Option<Vec<u8>>> {
+/// Find an included file, returning its path within the package and its contents.
+fn load_file_contents(fs: &mut impl FileSource, include_dirs: &[String], name: &str) -> anyhow::Result<Option<(RelativePathBuf, Vec<u8>)>> {
     let name = RelativePathBuf::from(name.replace('\\', "/"));
     let name = name.normalize();
 
     let test_path = RelativePath::new(INCLUDE_PATH).join(&name).normalize();
     if let Some(contents) = fs.read_file(test_path.as_str())? {
-        return Ok(Some(contents));
+        return Ok(Some((test_path, contents)));
     }
 
     for dir in include_dirs.iter() {
         let test_path = RelativePath::new(INCLUDE_PATH).join(dir).join(&name).normalize();
         if let Some(contents) = fs.read_file(test_path.as_str())? {
-            return Ok(Some(contents));
+            return Ok(Some((test_path, contents)));
         }
     }
 
     Ok(None)
 }
 
+fn parse_file(path: &str, contents: Vec<u8>) -> anyhow::Result<File> {
+    let contents = String::from_utf8(contents)
+        .map_err(|err| anyhow!("{}: {}", path, err))?;
+    File::parse_named(&contents, path)
+}
+
 fn load_file(fs: &mut impl FileSource, include_dirs: &[String], name: &str) -> anyhow::Result<Option<File>> {
-    Ok(load_file_contents(fs, include_dirs, name)?
-        .map(String::from_utf8)
-        .transpose()?
-        .map(|s| File::parse(&s))
-        .transpose()?)
+    load_file_contents(fs, include_dirs, name)?
+        .map(|(path, contents)| parse_file(path.as_str(), contents))
+        .transpose()
 }
 
-fn warn_filter<T>(result: anyhow::Result<T>) -> Option<T> {
-    match result {
-        Ok(v) => Some(v),
+/// Load one of an airport's optional include files, such as `EGLL.txi`,
+/// reporting it if it exists but can't be read.
+fn load_airport_include(fs: &mut impl FileSource, include_dirs: &[String], airport: &Airport, extension: &str,
+                        diagnostics: &mut Collector) -> Option<(String, impl Iterator<Item=anyhow::Result<Statement>>)> {
+    let name = format!("{}.{}", airport.identifier, extension);
+    match load_file(fs, include_dirs, &name) {
+        Ok(file) => file.and_then(|mut f| f.take_section(""))
+            .map(|section| (name, section.statements().to_vec().into_iter().map(Ok))),
         Err(err) => {
-            warn!("badly formatted input: {}", err);
+            diagnostics.report(Severity::Error, "include-error", &name, None, &err);
             None
         }
     }
 }
 
-struct SectionStatementIter<'a, S> {
-    fs: &'a mut S,
-    include_dirs: Vec<String>,
-    statements: VecDeque<Statement>,
-}
-
-impl<'a, S: FileSource> SectionStatementIter<'a, S> {
-    pub fn new(fs: &'a mut S, include_dirs: &[String], statements: &[Statement]) -> SectionStatementIter<'a, S> {
-        SectionStatementIter {
-            fs,
-            include_dirs: include_dirs.to_vec(),
-            statements: VecDeque::from_iter(statements.iter().cloned()),
-        }
-    }
-
-    pub fn from_section(fs: &'a mut S, include_dirs: &[String], section: Option<&Section>) -> SectionStatementIter<'a, S> {
-        Self::new(fs, include_dirs, section.as_ref().map_or(&[], |s| s.statements()))
-    }
-
-    fn load_file(&mut self, name: Option<&str>) -> anyhow::Result<()> {
-        let name = name.ok_or_else(|| anyhow!("missing filename"))?;
-        let file = load_file(self.fs, &self.include_dirs, name)?
-            .ok_or_else(|| anyhow!("missing referenced file: {}", name))?;
-
-        if file.sections().len() != 1 {
-            Err(anyhow!("unexpected sections in include: {}", name))?;
-        }
-
-        let section = match file.section("") {
-            Some(x) => x,
-            None => return Err(anyhow!("missing empty section")),
-        };
-
-        for statement in section.statements().iter().rev() {
-            self.statements.push_front(statement.clone());
-        }
-
-        Ok(())
-    }
-}
-
-impl<'a, S: FileSource> Iterator for SectionStatementIter<'a, S> {
-    type Item = anyhow::Result<Statement>;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        loop {
-            let next = match self.statements.pop_front() {
-                Some(x) => x,
-                None => return None,
-            };
-
-            let mut parts = next.parts();
-            if parts.next() == Some("F") {
-                // Load a new file
-                if let Err(x) = self.load_file(parts.next()) {
-                    return Some(Err(x));
-                }
-            } else {
-                break Some(Ok(next));
-            }
-        }
-    }
+/// Describe where something was read from, for prefixing messages.
+fn describe_location(location: Option<&Location>) -> String {
+    location.map_or(String::new(), |l| format!("{}: ", l))
 }
 
 #[derive(Debug, Clone)]
 pub struct SectorInfo {
-    pub center: (f64, f64),
+    pub center: GeoPosition,
     pub ratio: (f64, f64),
+    /// The magnetic variation, in degrees east.
     pub magnetic_variance: f64,
     pub include_dirs: Vec<String>,
 }
 
 impl SectorInfo {
     pub fn from_section(section: &Section) -> anyhow::Result<SectorInfo> {
-        let mut statements = section.statements().into_iter();
+        let mut statements = section.statements().iter();
 
         let lat = statements.next()
             .ok_or_else(|| anyhow!("missing latitude"))
-            .and_then(|s| Ok(parse_latitude(s.as_str())?))?;
+            .and_then(|s| parse_latitude(s.as_str()))?;
         let long = statements.next()
             .ok_or_else(|| anyhow!("missing longitude"))
-            .and_then(|s| Ok(parse_longitude(s.as_str())?))?;
+            .and_then(|s| parse_longitude(s.as_str()))?;
 
         let vert_ratio: f64 = statements.next()
             .ok_or_else(|| anyhow!("missing vertical ratio"))
@@ -163,7 +127,7 @@ impl SectorInfo {
                 s.parts().map(|p| p.replace('\\', "/")).collect()
             });
         Ok(SectorInfo {
-            center: (lat, long),
+            center: GeoPosition::new(lat, long),
             ratio: (vert_ratio, horiz_ratio),
             magnetic_variance,
             include_dirs,
@@ -196,193 +160,184 @@ pub struct Sector {
     pub geo: Vec<Geo>,
     pub fill_colors: Vec<FillColor>,
 
-    pub fix_lookup: HashMap<String, (f64, f64)>,
+    pub fix_lookup: HashMap<String, GeoPosition>,
+
+    /// How positions are turned into map coordinates, Mercator unless changed.
+    pub projection: Projection,
+
+    /// Where magnetic variation comes from, the sector-wide value in
+    /// [`SectorInfo`] (taken as degrees east) unless changed.
+    pub magnetic_model: MagneticModel,
+}
+
+/// Options controlling how a sector is parsed.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ParseOptions {
+    /// Fail with a [`DiagnosticsError`] if any problems are found, instead of
+    /// skipping the offending statements.
+    pub strict: bool,
+    /// The encoding of files which aren't UTF-8, or `None` to guess.
+    pub encoding: Option<&'static Encoding>,
 }
 
 impl Sector {
+    /// Parse a sector, logging and skipping anything which can't be parsed.
     pub fn parse(fs: &mut impl FileSource, name: &str) -> anyhow::Result<Sector> {
-        let root_file = File::parse(&String::from_utf8(fs.read_file(name)?
-            .ok_or_else(|| anyhow!("missing section main file"))?)?)?;
-
-        let info = root_file.section("INFO").ok_or(anyhow!("missing INFO section"))?;
-        let info = SectorInfo::from_section(info)?;
+        let (sector, diagnostics) = Sector::parse_with_diagnostics(fs, name, ParseOptions::default())?;
+        for diagnostic in diagnostics {
+            warn!("{}", diagnostic);
+        }
+        Ok(sector)
+    }
 
-        let fixes = SectionStatementIter::from_section(
-            fs, &info.include_dirs, root_file.section("FIXES"))
-            .filter_map(warn_filter)
-            .map(|s| Fix::parse(&s))
-            .filter_map(warn_filter)
-            .collect::<Vec<_>>();
+    /// Parse a sector, returning any problems found alongside it.
+    ///
+    /// Problems which stop the sector being parsed at all are still returned as errors.
+    pub fn parse_with_diagnostics(fs: &mut impl FileSource, name: &str, options: ParseOptions) -> anyhow::Result<(Sector, Vec<Diagnostic>)> {
+        let mut diagnostics = Collector::default();
+        let mut fs = DecodingSource::new(fs, options.encoding);
+        let sector = Sector::parse_collecting(&mut fs, name, &mut diagnostics)?;
+        let diagnostics = diagnostics.diagnostics;
+
+        if options.strict && !diagnostics.is_empty() {
+            return Err(DiagnosticsError { diagnostics }.into());
+        }
 
-        let ndbs = SectionStatementIter::from_section(
-            fs, &info.include_dirs, root_file.section("NDB"))
-            .filter_map(warn_filter)
-            .map(|s| NDB::parse(&s))
-            .filter_map(warn_filter)
-            .collect::<Vec<_>>();
+        Ok((sector, diagnostics))
+    }
 
-        let vors = SectionStatementIter::from_section(
-            fs, &info.include_dirs, root_file.section("VOR"))
-            .filter_map(warn_filter)
-            .map(|s| VOR::parse(&s))
-            .filter_map(warn_filter)
-            .collect::<Vec<_>>();
+    fn parse_collecting(fs: &mut impl FileSource, name: &str, diagnostics: &mut Collector) -> anyhow::Result<Sector> {
+        let mut root_file = parse_file(name, fs.read_file(name)?
+            .ok_or_else(|| anyhow!("missing section main file"))?)?;
 
-        let mut vrps = SectionStatementIter::from_section(
-            fs, &info.include_dirs, root_file.section("VFRFIX"))
-            .filter_map(warn_filter)
-            .map(|s| VRP::parse(&s))
-            .filter_map(warn_filter)
-            .collect::<Vec<_>>();
+        // Sector files in the wild often repeat sections, which all apply.
+        root_file.merge_duplicates();
 
-        let mut fix_lookup = HashMap::new();
-        let all_fixes = fixes.iter().map(|f| (&f.identifier, &f.geo_position))
-            .chain(ndbs.iter().map(|f| (&f.identifier, &f.geo_position)))
-            .chain(vors.iter().map(|f| (&f.identifier, &f.geo_position)))
-            .chain(vrps.iter().map(|f| (&f.identifier, &f.geo_position)));
-
-        for (name, (lat, long)) in all_fixes {
-            let parsed = parse_latitude(lat).and_then(|v| Ok((v, parse_longitude(long)?)));
-            let (lat, long) = match parsed {
-                Ok(v) => v,
-                Err(e) => {
-                    warn!("failed to parse fix {}: {}", name, e);
-                    continue;
-                }
-            };
-
-            fix_lookup.insert(name.to_string(), (lat, long));
-        }
+        let info = root_file.section("INFO").ok_or(anyhow!("missing INFO section"))?;
+        let info = SectorInfo::from_section(info)
+            .map_err(|err| anyhow!("{}: invalid INFO section: {}", name, err))?;
+        let mut sections = ExpandedSections::expand(fs, &info.include_dirs, name, &root_file);
+
+        let fixes = diagnostics.parse_statements(
+            "FIXES",
+            sections.take("FIXES"),
+            Fix::parse);
+
+        let ndbs = diagnostics.parse_statements(
+            "NDB",
+            sections.take("NDB"),
+            NDB::parse);
+
+        let vors = diagnostics.parse_statements(
+            "VOR",
+            sections.take("VOR"),
+            VOR::parse);
+
+        let mut vrps = diagnostics.parse_statements(
+            "VFRFIX",
+            sections.take("VFRFIX"),
+            VRP::parse);
+
+        let fix_lookup = fixes.iter().map(|f| (f.identifier.clone(), f.geo_position))
+            .chain(ndbs.iter().map(|f| (f.identifier.clone(), f.geo_position)))
+            .chain(vors.iter().map(|f| (f.identifier.clone(), f.geo_position)))
+            .chain(vrps.iter().map(|f| (f.identifier.clone(), f.geo_position)))
+            .collect();
 
-        let mut defines = HashMap::new();
-        for statement in SectionStatementIter::from_section(
-            fs, &info.include_dirs, root_file.section("DEFINE")) {
-            let statement = statement?;
-            let mut parts = statement.parts();
-            let name = parts.next()
-                .ok_or_else(|| anyhow!("missing define name"))?
-                .to_owned();
-            let fill_color = parse_colour(parts.next()
-                .ok_or_else(|| anyhow!("missing colour"))?)?;
-            defines.insert(name, fill_color);
-        }
+        let defines = diagnostics.parse_statements(
+            "DEFINE",
+            sections.take("DEFINE"),
+            |statement| {
+                let mut parts = statement.parts();
+                let name = parts.next()
+                    .ok_or_else(|| anyhow!("missing define name"))?
+                    .to_owned();
+                let fill_color = parse_colour(parts.next()
+                    .ok_or_else(|| anyhow!("missing colour"))?)?;
+                Ok((name, fill_color))
+            })
+            .into_iter()
+            .collect();
 
-        let airports = SectionStatementIter::from_section(
-            fs, &info.include_dirs, root_file.section("AIRPORT"))
-            .filter_map(warn_filter)
-            .map(|s| Airport::parse(&s))
-            .filter_map(warn_filter)
-            .collect::<Vec<_>>();
+        let airports = diagnostics.parse_statements(
+            "AIRPORT",
+            sections.take("AIRPORT"),
+            Airport::parse);
 
-        let runways = SectionStatementIter::from_section(
-            fs, &info.include_dirs, root_file.section("RUNWAY"))
-            .filter_map(warn_filter)
-            .map(|s| Runway::parse(&s))
-            .filter_map(warn_filter)
-            .collect();
+        let runways = diagnostics.parse_statements(
+            "RUNWAY",
+            sections.take("RUNWAY"),
+            Runway::parse);
 
-        let mut taxiways = SectionStatementIter::from_section(
-            fs, &info.include_dirs, root_file.section("TAXIWAY"))
-            .filter_map(warn_filter)
-            .map(|s| Taxiway::parse(&s))
-            .filter_map(warn_filter)
-            .collect::<Vec<_>>();
+        let mut taxiways = diagnostics.parse_statements(
+            "TAXIWAY",
+            sections.take("TAXIWAY"),
+            Taxiway::parse);
 
-        let mut gates = SectionStatementIter::from_section(
-            fs, &info.include_dirs, root_file.section("GATES"))
-            .filter_map(warn_filter)
-            .map(|s| Gate::parse(&s))
-            .filter_map(warn_filter)
-            .collect::<Vec<_>>();
+        let mut gates = diagnostics.parse_statements(
+            "GATES",
+            sections.take("GATES"),
+            Gate::parse);
 
         let mut airspaces = Vec::new();
         let mut airspaces_high = Vec::new();
         let mut airspaces_low = Vec::new();
 
-        Airspace::from_iterator(
-            &mut airspaces,
-            SectionStatementIter::from_section(
-                fs, &info.include_dirs, root_file.section("AIRSPACE")))?;
-        Airspace::from_iterator(
-            &mut airspaces,
-            SectionStatementIter::from_section(
-                fs, &info.include_dirs, root_file.section("ARTCC")))?;
-
-        Airspace::from_iterator(
-            &mut airspaces_high,
-            SectionStatementIter::from_section(
-                fs, &info.include_dirs, root_file.section("AIRSPACE_HIGH")))?;
-        Airspace::from_iterator(
-            &mut airspaces_high,
-            SectionStatementIter::from_section(
-                fs, &info.include_dirs, root_file.section("ARTCC_HIGH")))?;
-
-        Airspace::from_iterator(
-            &mut airspaces_low,
-            SectionStatementIter::from_section(
-                fs, &info.include_dirs, root_file.section("AIRSPACE_HIGH")))?;
-        Airspace::from_iterator(
-            &mut airspaces_low,
-            SectionStatementIter::from_section(
-                fs, &info.include_dirs, root_file.section("ARTCC_HIGH")))?;
+        for section in ["AIRSPACE", "ARTCC"] {
+            Airspace::from_iterator(&mut airspaces, section, sections.take(section), diagnostics);
+        }
+        for section in ["AIRSPACE_HIGH", "ARTCC_HIGH"] {
+            Airspace::from_iterator(&mut airspaces_high, section, sections.take(section), diagnostics);
+        }
+        for section in ["AIRSPACE_LOW", "ARTCC_LOW"] {
+            Airspace::from_iterator(&mut airspaces_low, section, sections.take(section), diagnostics);
+        }
 
         let mut airways_high = Vec::new();
         let mut airways_low = Vec::new();
 
         Airway::from_iterator(
             &mut airways_high,
-            SectionStatementIter::from_section(
-                fs, &info.include_dirs, root_file.section("HIGH AIRWAY")))?;
+            "HIGH AIRWAY",
+            sections.take("HIGH AIRWAY"),
+            diagnostics);
         Airway::from_iterator(
             &mut airways_low,
-            SectionStatementIter::from_section(
-                fs, &info.include_dirs, root_file.section("LOW AIRWAY")))?;
-
-        let geo = SectionStatementIter::from_section(
-            fs, &info.include_dirs, root_file.section("GEO"))
-            .filter_map(warn_filter)
-            .map(|s| Geo::parse(&s))
-            .filter_map(warn_filter)
-            .collect();
+            "LOW AIRWAY",
+            sections.take("LOW AIRWAY"),
+            diagnostics);
+
+        let geo = diagnostics.parse_statements(
+            "GEO",
+            sections.take("GEO"),
+            Geo::parse);
 
         let mut fill_colors = Vec::new();
         FillColor::from_iterator(
             &mut fill_colors,
-            SectionStatementIter::from_section(fs, &info.include_dirs, root_file.section("FILLCOLOR")))?;
-
-        let load_airport_include = |fs: &mut _, airport: &Airport, name: &str|
-                                    -> anyhow::Result<_> {
-            Ok(load_file(
-                fs, &info.include_dirs, &format!("{}.{}", &airport.identifier, name))?
-                .and_then(|mut f| f.take_section("")))
-        };
+            "FILLCOLOR",
+            sections.take("FILLCOLOR"),
+            diagnostics);
 
         for airport in airports.iter() {
-            if let Some(section) = load_airport_include(fs, airport, "vfi")? {
-                vrps.extend(section.statements().iter()
-                    .map(|s| VRP::parse(&s))
-                    .filter_map(warn_filter));
+            if let Some((name, statements)) = load_airport_include(fs, &info.include_dirs, airport, "vfi", diagnostics) {
+                vrps.extend(diagnostics.parse_statements(&name, statements, VRP::parse));
             }
 
-            if let Some(section) = load_airport_include(fs, airport, "tfl")? {
-                FillColor::from_iterator(
-                    &mut fill_colors,
-                    section.statements().iter().cloned().map(Ok))?;
+            if let Some((name, statements)) = load_airport_include(fs, &info.include_dirs, airport, "tfl", diagnostics) {
+                FillColor::from_iterator(&mut fill_colors, &name, statements, diagnostics);
             }
 
-            if let Some(section) = load_airport_include(fs, airport, "txi")? {
-                taxiways.extend(section.statements().iter()
-                    .map(|s| Taxiway::parse(s))
-                    .filter_map(warn_filter));
+            if let Some((name, statements)) = load_airport_include(fs, &info.include_dirs, airport, "txi", diagnostics) {
+                taxiways.extend(diagnostics.parse_statements(&name, statements, Taxiway::parse));
             }
 
-            if let Some(section) = load_airport_include(fs, airport, "gts")? {
-                gates.extend(section.statements().iter()
-                    .map(|s| Gate::parse(s))
-                    .filter_map(warn_filter));
+            if let Some((name, statements)) = load_airport_include(fs, &info.include_dirs, airport, "gts", diagnostics) {
+                gates.extend(diagnostics.parse_statements(&name, statements, Gate::parse));
             }
         }
 
+        let magnetic_model = MagneticModel::Fixed(info.magnetic_variance);
         Ok(Sector {
             info,
 
@@ -408,32 +363,38 @@ impl Sector {
             fill_colors,
 
             fix_lookup,
+            projection: Projection::Mercator,
+            magnetic_model,
         })
     }
 
     fn lookup_longitude(&self, name: &str) -> anyhow::Result<f64> {
-        if let Some((_, value)) = self.fix_lookup.get(name) {
-            Ok(*value)
+        if let Some(position) = self.fix_lookup.get(name) {
+            Ok(position.longitude)
         } else {
             parse_longitude(name)
         }
     }
 
     fn lookup_latitude(&self, name: &str) -> anyhow::Result<f64> {
-        if let Some((value, _)) = self.fix_lookup.get(name) {
-            Ok(*value)
+        if let Some(position) = self.fix_lookup.get(name) {
+            Ok(position.latitude)
         } else {
             parse_latitude(name)
         }
     }
 
-    pub fn lookup_geo_position(&self, latitude: &str, longitude: &str) -> anyhow::Result<(f64, f64)> {
-        Ok((self.lookup_latitude(latitude)?, self.lookup_longitude(longitude)?))
+    /// Resolve a position from a sector file, looking up any fixes it refers to.
+    pub fn lookup_geo_position(&self, position: &Position) -> anyhow::Result<GeoPosition> {
+        match position {
+            Position::Literal(position) => Ok(*position),
+            Position::Reference { latitude, longitude } =>
+                Ok(GeoPosition::new(self.lookup_latitude(latitude)?, self.lookup_longitude(longitude)?)),
+        }
     }
 
-    pub fn lookup_map_position(&self, latitude: &str, longitude: &str) -> anyhow::Result<(f64, f64)> {
-        let (latitude, longitude) = self.lookup_geo_position(latitude, longitude)?;
-        Ok(geo_to_map(latitude, longitude))
+    pub fn lookup_map_position(&self, position: &Position) -> anyhow::Result<(f64, f64)> {
+        Ok(self.projection.project(self.lookup_geo_position(position)?))
     }
 }
 
@@ -461,7 +422,177 @@ mod tests {
         ".into());
 
         let sector = Sector::parse(&mut fs, "Sector.isc").unwrap();
-        assert_abs_diff_eq!(sector.info.center.0, 23.215555, epsilon = 1e-6);
-        assert_abs_diff_eq!(sector.info.center.1, 60.034168, epsilon = 1e-6);
+        assert_abs_diff_eq!(sector.info.center.latitude, 60.034168, epsilon = 1e-6);
+        assert_abs_diff_eq!(sector.info.center.longitude, 23.215555, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn test_vor_declination() {
+        let mut fs: HashMap<String, Vec<u8>> = HashMap::new();
+        fs.insert("Sector.isc".into(), "[INFO]\nN060.00.00.000\nE023.00.00.000\n25\n25\n9\n\n\
+            [VOR]\nHEL;114.20;N060.19.00.000;E024.57.00.000;\n".into());
+
+        let mut sector = Sector::parse(&mut fs, "Sector.isc").unwrap();
+        let declination = |sector: &Sector| match sector.vors[0].to_domain(sector).unwrap().kind {
+            open_air::domain::PointKind::VOR(navaid) => navaid.declination,
+            kind => panic!("expected a VOR, got {:?}", kind),
+        };
+        assert_eq!(declination(&sector), Some(9.));
+
+        sector.magnetic_model = MagneticModel::Fixed(-3.);
+        assert_eq!(declination(&sector), Some(-3.));
+    }
+
+    #[test]
+    fn test_vor_dme_channel() {
+        let mut fs: HashMap<String, Vec<u8>> = HashMap::new();
+        fs.insert("Sector.isc".into(), "[INFO]\nN060.00.00.000\nE023.00.00.000\n25\n25\n0\n\n\
+            [VOR]\nHEL;114.20;N060.19.00.000;E024.57.00.000;\n".into());
+
+        let sector = Sector::parse(&mut fs, "Sector.isc").unwrap();
+        match sector.vors[0].to_domain(&sector).unwrap().kind {
+            open_air::domain::PointKind::VOR(navaid) => assert_eq!(navaid.dme_channel.as_deref(), Some("89X")),
+            kind => panic!("expected a VOR, got {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn test_include_locations() {
+        let mut fs: HashMap<String, Vec<u8>> = HashMap::new();
+        fs.insert("Sector.isc".into(), "[INFO]\nN060.00.00.000\nE023.00.00.000\n25\n25\n0\n\n[FIXES]\nF;fixes.fix\n".into());
+        fs.insert("Include/fixes.fix".into(), "ABC;N060.00.00.000;E023.00.00.000;\n\nDEF;N060.00.00.000\n".into());
+
+        let root = File::parse_named("[FIXES]\nF;fixes.fix\n", "Sector.isc").unwrap();
+        let statements = ExpandedSections::expand(&mut fs, &[], "Sector.isc", &root)
+            .take("FIXES")
+            .collect::<anyhow::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(statements[1].location().unwrap().to_string(), "Include/fixes.fix:3");
+
+        let err = statements[1].parse_with(Fix::parse).unwrap_err();
+        assert!(format!("{:#}", err).starts_with("Include/fixes.fix:3: "));
+
+        let sector = Sector::parse(&mut fs, "Sector.isc").unwrap();
+        assert_eq!(sector.fixes.len(), 1);
+        assert_eq!(sector.fixes[0].location.as_ref().unwrap().line, 1);
+
+        let root = File::parse_named("[INFO]\n\n\n\n\n\n\n[FIXES]\nF;missing.fix\n", "Sector.isc").unwrap();
+        let err = ExpandedSections::expand(&mut fs, &[], "Sector.isc", &root)
+            .take("FIXES")
+            .next().unwrap().unwrap_err();
+        assert_eq!(format!("{:#}", err), "Sector.isc:9: missing referenced file: missing.fix");
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let mut fs: HashMap<String, Vec<u8>> = HashMap::new();
+        fs.insert("Sector.isc".into(), "[INFO]\nN060.00.00.000\nE023.00.00.000\n25\n25\n0\n\n\
+            [FIXES]\nABC;N060.00.00.000;E023.00.00.000;\nDEF\nF;missing.fix\n".into());
+
+        let (sector, diagnostics) = Sector::parse_with_diagnostics(
+            &mut fs, "Sector.isc", ParseOptions::default()).unwrap();
+        assert_eq!(sector.fixes.len(), 1);
+        assert_eq!(diagnostics.len(), 2);
+
+        let first = &diagnostics[0];
+        assert_eq!(first.severity, Severity::Warning);
+        assert_eq!(first.section, "FIXES");
+        assert_eq!(first.text.as_deref(), Some("DEF"));
+        assert_eq!(first.location.as_ref().unwrap().to_string(), "Sector.isc:10");
+
+        let second = &diagnostics[1];
+        assert_eq!(second.severity, Severity::Error);
+        assert_eq!(second.location.as_ref().unwrap().line, 11);
+        assert_eq!(second.message, "missing referenced file: missing.fix");
+
+        let json = serde_json::to_value(&diagnostics).unwrap();
+        assert_eq!(json[0]["location"]["path"], "Sector.isc");
+
+        let err = Sector::parse_with_diagnostics(&mut fs, "Sector.isc", ParseOptions { strict: true, ..Default::default() })
+            .unwrap_err();
+        assert_eq!(err.downcast_ref::<DiagnosticsError>().unwrap().diagnostics, diagnostics);
+    }
+
+    #[test]
+    fn test_positions() {
+        let mut fs: HashMap<String, Vec<u8>> = HashMap::new();
+        fs.insert("Sector.isc".into(), "[INFO]\nN060.00.00.000\nE023.00.00.000\n25\n25\n0\n\n\
+            [FIXES]\nABC;N060.30.00.000;E023.30.00.000;\nDEF;N091.00.00.000;E023.00.00.000;\n\
+            [GEO]\nN060.00.00.000;E023.00.00.000;ABC;ABC;COAST\n\
+            N060.0A.00.000;E023.00.00.000;ABC;ABC;COAST\n".into());
+
+        let (sector, diagnostics) = Sector::parse_with_diagnostics(
+            &mut fs, "Sector.isc", ParseOptions::default()).unwrap();
+        assert_eq!(sector.fixes.len(), 1);
+        assert_eq!(sector.geo.len(), 1);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].code, "invalid-statement");
+        assert_eq!(diagnostics[0].location.as_ref().unwrap().line, 10);
+        assert_eq!(diagnostics[1].code, "invalid-statement");
+        assert_eq!(diagnostics[1].location.as_ref().unwrap().line, 13);
+        assert!(diagnostics[1].message.starts_with("invalid latitude N060.0A.00.000"), "{}", diagnostics[1].message);
+
+        let geo = &sector.geo[0];
+        assert_eq!(geo.start, Position::Literal(GeoPosition::new(60., 23.)));
+        assert_eq!(geo.end, Position::Reference { latitude: "ABC".into(), longitude: "ABC".into() });
+        assert_eq!(sector.lookup_geo_position(&geo.end).unwrap(), GeoPosition::new(60.5, 23.5));
+    }
+
+    #[test]
+    fn test_section_diagnostics() {
+        let mut fs: HashMap<String, Vec<u8>> = HashMap::new();
+        fs.insert("Sector.isc".into(), "[INFO]\nN060.00.00.000\nE023.00.00.000\n25\n25\n0\n\n\
+            [DEFINE]\nRED;#FF0000\nBLUE\n\
+            [AIRSPACE]\nT;EFHK;N060.00.00.000;E023.00.00.000;\nF;missing_airspace.txt\n\
+            T;EFTU;N060.00.00.000;E023.00.00.000;\nX;EFTP\nT;EFHK;N061.00.00.000;E024.00.00.000;\n\
+            T;EFTP;N060.00.00.000;E023.00.00.000;\nT;EFJY;N060.00.00.000;E023.00.00.000;\n\
+            T;EFOU;N060.00.00.000;E023.00.00.000;\nT;EFRO;N060.00.00.000;E023.00.00.000;\n\
+            [HIGH AIRWAY]\nT;UL9;N060.00.00.000;E023.00.00.000;\nF;missing_airway.txt\n\
+            T;UN14;N060.00.00.000;E023.00.00.000;\nT;UL9;N061.00.00.000;E024.00.00.000;\n\
+            [FILLCOLOR]\nLAKE;#0000FF;wide;#0000FF\nN060.00.00.000;E023.00.00.000;\n\
+            WATER;#0000FF;1;#0000FF;0;\nN060.00.00.000;E023.00.00.000;\nN061.00.00.000\n".into());
+
+        let (sector, diagnostics) = Sector::parse_with_diagnostics(
+            &mut fs, "Sector.isc", ParseOptions::default()).unwrap();
+
+        assert_eq!(sector.defines.len(), 1);
+        let airspaces = sector.airspaces.iter().map(|a| a.identifier.as_str()).collect::<Vec<_>>();
+        assert_eq!(airspaces, vec!["EFHK", "EFTU", "EFTP", "EFJY", "EFOU", "EFRO"]);
+        assert_eq!(sector.airspaces[0].geo_points.len(), 2);
+        let airways = sector.airways_high.iter().map(|a| a.identifier.as_str()).collect::<Vec<_>>();
+        assert_eq!(airways, vec!["UL9", "UN14"]);
+        assert_eq!(sector.fill_colors.len(), 1);
+        assert_eq!(sector.fill_colors[0].geo_points.len(), 1);
+
+        let summary = diagnostics.iter()
+            .map(|d| (d.code, d.section.as_str(), d.location.as_ref().unwrap().line))
+            .collect::<Vec<_>>();
+        assert_eq!(summary, vec![
+            ("invalid-statement", "DEFINE", 10),
+            ("include-error", "AIRSPACE", 13),
+            ("invalid-statement", "AIRSPACE", 15),
+            ("include-error", "HIGH AIRWAY", 23),
+            ("invalid-statement", "FILLCOLOR", 27),
+            ("invalid-statement", "FILLCOLOR", 31),
+        ]);
+        assert_eq!(diagnostics[1].message, "missing referenced file: missing_airspace.txt");
+        assert_eq!(diagnostics[3].message, "missing referenced file: missing_airway.txt");
+    }
+
+    #[test]
+    fn test_encoding() {
+        let mut fs: HashMap<String, Vec<u8>> = HashMap::new();
+        fs.insert("Sector.isc".into(), b"[INFO]\nN045.00.00.000\nE004.00.00.000\n25\n25\n0\n\n\
+            [AIRPORT]\nLFLY;659;;N045.43.38.000;E004.56.40.000;Lyon Bron A\xE9roport;\n".to_vec());
+
+        let sector = Sector::parse(&mut fs, "Sector.isc").unwrap();
+        assert_eq!(sector.airports[0].name, "Lyon Bron A\u{e9}roport");
+
+        let options = ParseOptions {
+            encoding: Some(encoding_rs::KOI8_R),
+            ..Default::default()
+        };
+        let (sector, _) = Sector::parse_with_diagnostics(&mut fs, "Sector.isc", options).unwrap();
+        assert_eq!(sector.airports[0].name, "Lyon Bron A\u{418}roport");
     }
 }
@@ -1,30 +1,81 @@
+use std::fmt::{self, Display, Formatter};
+
 use anyhow::anyhow;
 
+use open_air::domain::coords::GeoPosition;
+
+use crate::aurora::gdf::{parse_latitude, parse_longitude};
 use crate::aurora::sector::Sector;
 
-pub fn parse_string_position<'a>(src: &mut impl Iterator<Item=&'a str>) -> anyhow::Result<(String, String)> {
+/// A position from a sector file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Position {
+    /// Coordinates given directly.
+    Literal(GeoPosition),
+    /// A position where either half names a fix, which can only be resolved
+    /// once every fix in the sector has been read.
+    Reference {
+        latitude: String,
+        longitude: String,
+    },
+}
+
+impl Display for Position {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Position::Literal(position) => write!(f, "{}", position),
+            Position::Reference { latitude, longitude } => write!(f, "{};{}", latitude, longitude),
+        }
+    }
+}
+
+fn parse_string_position<'a>(src: &mut impl Iterator<Item=&'a str>) -> anyhow::Result<(&'a str, &'a str)> {
     let latitude = src.next()
-        .ok_or_else(|| anyhow!("missing latitude"))?
-        .to_owned();
+        .ok_or_else(|| anyhow!("missing latitude"))?;
     let longitude = src.next()
-        .ok_or_else(|| anyhow!("missing longitude"))?
-        .to_owned();
+        .ok_or_else(|| anyhow!("missing longitude"))?;
     Ok((latitude, longitude))
 }
 
-pub fn frequency_to_int(freq: &str) -> anyhow::Result<u16> {
-    let freq = if let Some(offset) = freq.find('.') {
-        let just_digits = format!("{}{}", &freq[..offset], &freq[offset + 1..]);
-        let value = 10u16.pow((freq.len() - offset) as u32);
-        just_digits.parse::<u16>()? * (100 / value)
-    } else {
-        freq.parse::<u16>()?
-    };
-    Ok(freq)
+/// Whether half of a position is meant as a coordinate rather than a fix name,
+/// because it starts with a hemisphere followed by a digit.
+fn looks_like_coordinate(src: &str, hemispheres: [char; 2]) -> bool {
+    let mut chars = src.chars();
+    matches!((chars.next(), chars.next()), (Some(h), Some(d)) if hemispheres.contains(&h) && d.is_ascii_digit())
+}
+
+/// Parse a position which may refer to fixes.
+///
+/// Either half is taken as a fix name unless it looks like a coordinate, in
+/// which case it must be a valid one.
+pub fn parse_position<'a>(src: &mut impl Iterator<Item=&'a str>) -> anyhow::Result<Position> {
+    let (latitude, longitude) = parse_string_position(src)?;
+    if let Ok(position) = GeoPosition::parse(latitude, longitude) {
+        return Ok(Position::Literal(position));
+    }
+
+    if looks_like_coordinate(latitude, ['N', 'S']) {
+        parse_latitude(latitude)
+            .map_err(|err| anyhow!("invalid latitude {}: {}", latitude, err))?;
+    }
+    if looks_like_coordinate(longitude, ['E', 'W']) {
+        parse_longitude(longitude)
+            .map_err(|err| anyhow!("invalid longitude {}: {}", longitude, err))?;
+    }
+    Ok(Position::Reference {
+        latitude: latitude.to_owned(),
+        longitude: longitude.to_owned(),
+    })
+}
+
+/// Parse a position which must be given as coordinates, such as that of a fix.
+pub fn parse_geo_position<'a>(src: &mut impl Iterator<Item=&'a str>) -> anyhow::Result<GeoPosition> {
+    let (latitude, longitude) = parse_string_position(src)?;
+    Ok(GeoPosition::parse(latitude, longitude)?)
 }
 
-pub fn convert_geo_points<'a>(sector: &Sector, points: impl Iterator<Item=&'a (String, String)>)
+pub fn convert_geo_points<'a>(sector: &Sector, points: impl Iterator<Item=&'a Position>)
                               -> anyhow::Result<Vec<(f64, f64)>> {
-    points.map(|(lat, long)| sector.lookup_map_position(&lat, &long))
+    points.map(|position| sector.lookup_map_position(position))
         .collect()
 }
@@ -2,22 +2,24 @@ use anyhow::anyhow;
 
 use open_air::domain::viewer::Colour;
 
-use crate::aurora::gdf::{parse_colour, Statement};
-use crate::aurora::sector::parsing::parse_string_position;
+use crate::aurora::gdf::{Location, parse_colour, Statement};
+use crate::aurora::sector::diagnostics::Collector;
+use crate::aurora::sector::parsing::{parse_position, Position};
 
 #[derive(Debug, Clone)]
 pub struct Geo {
-    pub start: (String, String),
-    pub end: (String, String),
+    pub start: Position,
+    pub end: Position,
     pub color: Option<Colour>,
+    pub location: Option<Location>,
 }
 
 impl Geo {
     pub fn parse(statement: &Statement) -> anyhow::Result<Geo> {
         let mut parts = statement.parts();
 
-        let start = parse_string_position(&mut parts)?;
-        let end = parse_string_position(&mut parts)?;
+        let start = parse_position(&mut parts)?;
+        let end = parse_position(&mut parts)?;
         let color = parts.next()
             .map(parse_colour)
             .transpose()?;
@@ -26,6 +28,7 @@ impl Geo {
             start,
             end,
             color,
+            location: statement.location().cloned(),
         })
     }
 }
@@ -38,7 +41,8 @@ pub struct FillColor {
     pub stroke_color: Colour,
     pub fill_color_clear: bool,
     pub filter: String,
-    pub geo_points: Vec<(String, String)>,
+    pub geo_points: Vec<Position>,
+    pub location: Option<Location>,
 }
 
 impl FillColor {
@@ -55,7 +59,7 @@ impl FillColor {
             .parse::<f32>()?;
         let stroke_color = parse_colour(parts.next()
             .ok_or_else(|| anyhow!("missing stroke colour"))?)?;
-        let fill_color_clear = parts.next().map_or(false, |v| v == "1");
+        let fill_color_clear = parts.next() == Some("1");
         let filter = parts.next()
             .map_or(String::new(), |v| v.to_owned());
 
@@ -67,48 +71,66 @@ impl FillColor {
             fill_color_clear,
             filter,
             geo_points: vec![],
+            location: statement.location().cloned(),
         })
     }
 
+    /// Whether a statement is a point of a fill colour, rather than the start
+    /// of a new one.
+    fn is_point(statement: &Statement) -> bool {
+        statement.parts().count() < 4
+    }
+
     pub fn add_point(&mut self, statement: &Statement) -> anyhow::Result<bool> {
-        if statement.parts().count() < 4 {
-            let mut parts = statement.parts();
-            let lat = parts.next()
-                .ok_or_else(|| anyhow!("missing latitude"))?
-                .to_owned();
-            let long = parts.next()
-                .ok_or_else(|| anyhow!("missing longitude"))?
-                .to_owned();
-
-            self.geo_points.push((lat, long));
+        if FillColor::is_point(statement) {
+            self.geo_points.push(parse_position(&mut statement.parts())?);
             Ok(true)
         } else {
             Ok(false)
         }
     }
 
-    pub fn from_iterator(dest: &mut Vec<FillColor>, src: impl Iterator<Item=anyhow::Result<Statement>>) -> anyhow::Result<()> {
+    /// Parse the statements of a fill colour section, where each fill colour is
+    /// followed by its points.
+    ///
+    /// The points of a fill colour which can't be parsed are skipped with it.
+    pub(crate) fn from_iterator(dest: &mut Vec<FillColor>, section: &str,
+                                src: impl Iterator<Item=anyhow::Result<Statement>>,
+                                diagnostics: &mut Collector) {
         let mut current: Option<FillColor> = None;
+        let mut skipping = false;
 
-        let mut flush = |value: &mut Option<FillColor>| {
-            if let Some(value) = value.take() {
-                dest.push(value);
+        for statement in src {
+            let statement = match diagnostics.statement(section, statement) {
+                Some(x) => x,
+                None => continue,
+            };
+
+            if FillColor::is_point(&statement) {
+                let result = match current.as_mut() {
+                    Some(fill) => fill.add_point(&statement).map(|_| ()),
+                    None if skipping => Ok(()),
+                    None => Err(anyhow!("point outside of a fill colour")),
+                };
+                if let Err(err) = result {
+                    diagnostics.invalid_statement(section, &statement, &err);
+                }
+                continue;
             }
-        };
 
-        for statement in src {
-            let statement = statement?;
-            let did_add = current.as_mut()
-                .map(|c| c.add_point(&statement))
-                .transpose()?
-                .unwrap_or(false);
-            if !did_add {
-                flush(&mut current);
-                current = Some(FillColor::parse(&statement)?);
+            dest.extend(current.take());
+            match FillColor::parse(&statement) {
+                Ok(fill) => {
+                    current = Some(fill);
+                    skipping = false;
+                }
+                Err(err) => {
+                    diagnostics.invalid_statement(section, &statement, &err);
+                    skipping = true;
+                }
             }
         }
 
-        flush(&mut current);
-        Ok(())
+        dest.extend(current);
     }
 }
@@ -1,32 +1,146 @@
 use clap::Clap;
+use std::collections::BTreeMap;
 use std::path::PathBuf;
-use ivao::aurora::sector::{DirectorySource, Sector};
+use anyhow::anyhow;
+use encoding_rs::Encoding;
+use ivao::aurora::sector::{encoding_for_label, open_package, DiagnosticsError, FileReport, OverlaySource, ParseOptions, Sector, Severity};
+use open_air::domain::coords::{GeoPosition, Projection};
 use open_air::domain::viewer::SectionBuilder;
 
 #[derive(Clap)]
 struct Opts {
+    /// The sector package, as a directory or a zip file.
     #[clap(short, long)]
     input: PathBuf,
 
+    /// Directories or zip files to layer over the input, highest priority first.
+    #[clap(long)]
+    overlay: Vec<PathBuf>,
+
     #[clap(short, long)]
     output: PathBuf,
 
+    /// Fail if any problems are found in the sector files.
+    #[clap(long)]
+    strict: bool,
+
+    /// Print the problems found in each sector file as `summary` or `json`.
+    #[clap(long, possible_values = &["summary", "json"])]
+    report: Option<String>,
+
+    /// The encoding of sector files which aren't UTF-8, such as `windows-1252`.
+    /// Guessed from the contents of each file if not given.
+    #[clap(long, parse(try_from_str = encoding_for_label))]
+    encoding: Option<&'static Encoding>,
+
+    /// How to project positions onto the map: `mercator`, `stereographic`
+    /// around the centre of the first sector, or `lambert` conformal conic with
+    /// standard parallels 5° either side of it.
+    #[clap(long, default_value = "mercator", possible_values = &["mercator", "stereographic", "lambert"])]
+    projection: String,
+
     sector_files: Vec<String>,
 }
 
+fn print_summary(reports: &[FileReport]) {
+    for report in reports {
+        let mut counts: BTreeMap<(&str, Severity), usize> = BTreeMap::new();
+        for diagnostic in report.diagnostics.iter() {
+            *counts.entry((&diagnostic.section, diagnostic.severity)).or_default() += 1;
+        }
+
+        println!("{}: {} problem(s)", report.file, report.diagnostics.len());
+        for ((section, severity), count) in counts {
+            println!("  [{}] {} {}(s)", section, count, severity);
+        }
+        for diagnostic in report.diagnostics.iter() {
+            println!("  {}", diagnostic);
+        }
+    }
+}
+
+fn projection_for(kind: &str, center: GeoPosition) -> Projection {
+    match kind {
+        "stereographic" => Projection::Stereographic {
+            center,
+        },
+        "lambert" => Projection::LambertConformalConic {
+            origin: center,
+            // Keep both parallels short of the poles, where the cone is undefined.
+            standard_parallels: ((center.latitude - 5.).max(-89.), (center.latitude + 5.).min(89.)),
+        },
+        _ => Projection::Mercator,
+    }
+}
+
 fn main() -> anyhow::Result<()> {
     env_logger::init();
 
     let opts = Opts::parse();
-    let mut source = DirectorySource::new(opts.input.clone())?;
-    let mut builder = SectionBuilder::new(9);
+    let mut source = OverlaySource::new();
+    for path in opts.overlay.iter().chain(std::iter::once(&opts.input)) {
+        source.add_layer(&path.display().to_string(), open_package(path.clone())?);
+    }
+    let mut builder: Option<SectionBuilder> = None;
+    let options = ParseOptions {
+        strict: opts.strict,
+        encoding: opts.encoding,
+    };
 
+    let mut reports = Vec::new();
+    let mut failed = false;
     for path in opts.sector_files {
-        let sector = Sector::parse(&mut source, &path)?;
-        sector.convert(&mut builder)?;
+        let diagnostics = match Sector::parse_with_diagnostics(&mut source, &path, options) {
+            Ok((mut sector, diagnostics)) => {
+                // Every sector shares the projection and variation of the first.
+                let builder = builder.get_or_insert_with(|| {
+                    let mut builder = SectionBuilder::with_projection(
+                        9, projection_for(&opts.projection, sector.info.center));
+                    builder.global_mut().magnetic_variation = sector.magnetic_model.variation(sector.info.center);
+                    builder
+                });
+                sector.projection = builder.projection();
+                sector.convert(builder)?;
+                diagnostics
+            }
+            Err(err) => match err.downcast::<DiagnosticsError>() {
+                Ok(err) => {
+                    failed = true;
+                    err.diagnostics
+                }
+                Err(err) => return Err(err),
+            },
+        };
+
+        if opts.report.is_none() {
+            for diagnostic in diagnostics.iter() {
+                log::warn!("{}", diagnostic);
+            }
+        }
+
+        reports.push(FileReport {
+            file: path,
+            diagnostics,
+        });
+    }
+
+    let mut origins = source.origins().collect::<Vec<_>>();
+    origins.sort();
+    for (path, layer) in origins {
+        log::info!("read {} from {}", path, layer);
+    }
+
+    match opts.report.as_deref() {
+        Some("json") => println!("{}", serde_json::to_string_pretty(&reports)?),
+        Some(_) => print_summary(&reports),
+        None => {}
+    }
+
+    if failed {
+        return Err(anyhow!("problems found in sector files in strict mode"));
     }
 
-    let (global, sections) = builder.build();
+    let (global, sections) = builder.unwrap_or_else(|| SectionBuilder::new(9)).build();
     for section in sections {
         let name = format!("section_{:03}_{:03}_{:03}.json", section.division.0, section.division.1, section.division.2);
         let contents = serde_json::to_string_pretty(&section)?;
@@ -0,0 +1,108 @@
+use clap::Clap;
+use std::path::PathBuf;
+use anyhow::anyhow;
+use encoding_rs::Encoding;
+use ivao::aurora::sector::{encoding_for_label, lint, open_package, FileReport, OverlaySource, ParseOptions, Severity};
+use open_air::domain::magnetic::{MagneticModel, WorldMagneticModel};
+
+#[derive(Clap)]
+struct Opts {
+    /// The sector package, as a directory or a zip file.
+    #[clap(short, long)]
+    input: PathBuf,
+
+    /// Directories or zip files to layer over the input, highest priority first.
+    #[clap(long)]
+    overlay: Vec<PathBuf>,
+
+    /// Print the problems found as `json` or `text`.
+    #[clap(long, default_value = "json", possible_values = &["json", "text"])]
+    format: String,
+
+    /// Fail on warnings as well as errors.
+    #[clap(long)]
+    deny_warnings: bool,
+
+    /// The encoding of sector files which aren't UTF-8, such as `windows-1252`.
+    /// Guessed from the contents of each file if not given.
+    #[clap(long, parse(try_from_str = encoding_for_label))]
+    encoding: Option<&'static Encoding>,
+
+    /// Check runways against the World Magnetic Model on this decimal year,
+    /// such as `2024.5`, instead of each sector's own magnetic variation.
+    #[clap(long)]
+    magnetic_year: Option<f64>,
+
+    /// A `WMM.COF` coefficient file to use instead of the built-in WMM2020.
+    #[clap(long, requires = "magnetic-year")]
+    magnetic_model: Option<PathBuf>,
+
+    sector_files: Vec<String>,
+}
+
+fn main() -> anyhow::Result<()> {
+    env_logger::init();
+
+    let opts = Opts::parse();
+    let mut source = OverlaySource::new();
+    for path in opts.overlay.iter().chain(std::iter::once(&opts.input)) {
+        source.add_layer(&path.display().to_string(), open_package(path.clone())?);
+    }
+
+    let options = ParseOptions {
+        encoding: opts.encoding,
+        ..Default::default()
+    };
+
+    let magnetic_model = match (opts.magnetic_year, &opts.magnetic_model) {
+        (Some(year), Some(path)) => {
+            let model = WorldMagneticModel::parse(&std::fs::read_to_string(path)?)
+                .map_err(|err| anyhow!("failed to read {}: {}", path.display(), err))?;
+            Some(MagneticModel::World { model, year })
+        }
+        (Some(year), None) => Some(MagneticModel::world(year)),
+        _ => None,
+    };
+    if let Some(MagneticModel::World { model, year }) = &magnetic_model {
+        if !model.is_valid_for(*year) {
+            log::warn!("the magnetic model for {} isn't meant to be used in {}, pass a newer one with --magnetic-model",
+                       model.epoch, year);
+        }
+    }
+
+    let mut reports = Vec::new();
+    for path in opts.sector_files {
+        let diagnostics = lint(&mut source, &path, options, magnetic_model.as_ref());
+        reports.push(FileReport {
+            file: path,
+            diagnostics,
+        });
+    }
+
+    let mut origins = source.origins().collect::<Vec<_>>();
+    origins.sort();
+    for (path, layer) in origins {
+        log::info!("read {} from {}", path, layer);
+    }
+
+    if opts.format == "json" {
+        println!("{}", serde_json::to_string_pretty(&reports)?);
+    } else {
+        for report in reports.iter() {
+            for diagnostic in report.diagnostics.iter() {
+                println!("{}: {}", report.file, diagnostic);
+            }
+        }
+    }
+
+    let threshold = if opts.deny_warnings { Severity::Warning } else { Severity::Error };
+    let failures = reports.iter()
+        .flat_map(|r| r.diagnostics.iter())
+        .filter(|d| d.severity >= threshold)
+        .count();
+    if failures > 0 {
+        return Err(anyhow!("found {} problem(s) in sector files", failures));
+    }
+
+    Ok(())
+}
@@ -1 +1,2 @@
 pub mod aurora;
+pub mod whazzup;
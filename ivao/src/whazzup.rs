@@ -0,0 +1,265 @@
+//! A module for parsing the IVAO whazzup v2 feed, which describes who is
+//! currently connected to the network.
+//!
+//! The feed is deserialized into typed structures, and each track has its map
+//! position calculated so that it can be handed directly to the viewer. Map
+//! positions are Web Mercator unless the tracks are projected again to match
+//! the sector tiles.
+
+use serde::{Deserialize, Serialize};
+
+use open_air::domain::coords::{GeoPosition, Projection};
+
+/// The URL of the public whazzup v2 feed.
+pub const WHAZZUP_V2_URL: &str = "https://api.ivao.aero/v2/tracker/whazzup";
+
+/// The last reported position of a client.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Track {
+    pub altitude: f64,
+    pub altitude_difference: f64,
+    pub arrival_distance: Option<f64>,
+    pub departure_distance: Option<f64>,
+    pub ground_speed: f64,
+    pub heading: f64,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub on_ground: bool,
+    pub state: Option<String>,
+    pub timestamp: String,
+    pub transponder: u32,
+    pub transponder_mode: Option<String>,
+    pub time: u64,
+
+    /// The position on the map, calculated from `latitude` and `longitude`.
+    pub map_x: f64,
+    pub map_y: f64,
+}
+
+impl Track {
+    /// Calculate the map position from the geographic position, using Web Mercator.
+    pub fn project(&mut self) {
+        self.project_with(&Projection::Mercator);
+    }
+
+    /// Calculate the map position from the geographic position.
+    pub fn project_with(&mut self, projection: &Projection) {
+        let (x, y) = projection.project(GeoPosition::new(self.latitude, self.longitude));
+        self.map_x = x;
+        self.map_y = y;
+    }
+
+    pub fn map_position(&self) -> (f64, f64) {
+        (self.map_x, self.map_y)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Aircraft {
+    pub icao_code: String,
+    pub model: Option<String>,
+    pub wake_turbulence: Option<String>,
+    pub is_military: bool,
+    pub description: Option<String>,
+}
+
+/// A flight plan, as filed by a pilot.
+///
+/// Durations (`eet`, `endurance`) are in seconds, and times of day
+/// (`departure_time`, `actual_departure_time`) are in seconds since midnight UTC.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct FlightPlan {
+    pub id: u64,
+    pub revision: u32,
+    pub aircraft_id: Option<String>,
+    pub aircraft_number: u32,
+    pub departure_id: Option<String>,
+    pub arrival_id: Option<String>,
+    pub alternative_id: Option<String>,
+    pub alternative2_id: Option<String>,
+    pub route: Option<String>,
+    pub remarks: Option<String>,
+    pub speed: Option<String>,
+    pub level: Option<String>,
+    pub flight_rules: Option<String>,
+    pub flight_type: Option<String>,
+    pub eet: Option<u64>,
+    pub endurance: Option<u64>,
+    pub departure_time: Option<u64>,
+    pub actual_departure_time: Option<u64>,
+    pub people_on_board: Option<u32>,
+    pub created_at: Option<String>,
+    pub aircraft_equipments: Option<String>,
+    pub aircraft_transponder_types: Option<String>,
+    pub aircraft: Option<Aircraft>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Pilot {
+    pub id: u64,
+    pub user_id: u64,
+    pub callsign: String,
+    pub server_id: String,
+    pub software_type_id: Option<String>,
+    pub software_version: Option<String>,
+    pub rating: i32,
+    pub created_at: String,
+    /// The number of seconds the client has been connected.
+    pub time: u64,
+    pub last_track: Option<Track>,
+    pub flight_plan: Option<FlightPlan>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct AtcSession {
+    /// The primary frequency in MHz.
+    pub frequency: f64,
+    pub position: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Atis {
+    pub lines: Vec<String>,
+    pub revision: Option<String>,
+    pub timestamp: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Atc {
+    pub id: u64,
+    pub user_id: u64,
+    pub callsign: String,
+    pub server_id: String,
+    pub software_type_id: Option<String>,
+    pub software_version: Option<String>,
+    pub rating: i32,
+    pub created_at: String,
+    pub time: u64,
+    pub last_track: Option<Track>,
+    pub atc_session: Option<AtcSession>,
+    pub atis: Option<Atis>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Observer {
+    pub id: u64,
+    pub user_id: u64,
+    pub callsign: String,
+    pub server_id: String,
+    pub software_type_id: Option<String>,
+    pub software_version: Option<String>,
+    pub rating: i32,
+    pub created_at: String,
+    pub time: u64,
+    pub last_track: Option<Track>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Clients {
+    pub pilots: Vec<Pilot>,
+    pub atcs: Vec<Atc>,
+    pub observers: Vec<Observer>,
+}
+
+/// A single snapshot of the whazzup feed.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Whazzup {
+    pub updated_at: String,
+    pub clients: Clients,
+}
+
+impl Whazzup {
+    /// Parse a whazzup v2 JSON document, calculating the map position of every track.
+    pub fn parse(src: &[u8]) -> anyhow::Result<Whazzup> {
+        let mut whazzup: Whazzup = serde_json::from_slice(src)?;
+        whazzup.project();
+        Ok(whazzup)
+    }
+
+    /// Recalculate the map position of every track, using Web Mercator.
+    pub fn project(&mut self) {
+        self.project_with(&Projection::Mercator);
+    }
+
+    /// Recalculate the map position of every track.
+    pub fn project_with(&mut self, projection: &Projection) {
+        let tracks = self.clients.pilots.iter_mut().map(|c| &mut c.last_track)
+            .chain(self.clients.atcs.iter_mut().map(|c| &mut c.last_track))
+            .chain(self.clients.observers.iter_mut().map(|c| &mut c.last_track));
+        for track in tracks.flatten() {
+            track.project_with(projection);
+        }
+    }
+
+    /// Find a pilot by callsign.
+    pub fn pilot(&self, callsign: &str) -> Option<&Pilot> {
+        self.clients.pilots.iter().find(|p| p.callsign == callsign)
+    }
+
+    /// Find a controller by callsign.
+    pub fn atc(&self, callsign: &str) -> Option<&Atc> {
+        self.clients.atcs.iter().find(|a| a.callsign == callsign)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use open_air::domain::coords::geo_to_map;
+
+    use super::*;
+
+    const FIXTURE: &[u8] = include_bytes!("../fixtures/whazzup_v2.json");
+
+    #[test]
+    fn test_parse() {
+        let whazzup = Whazzup::parse(FIXTURE).unwrap();
+        assert_eq!(whazzup.updated_at, "2021-10-16T18:30:12.000Z");
+        assert_eq!(whazzup.clients.pilots.len(), 2);
+        assert_eq!(whazzup.clients.atcs.len(), 1);
+        assert_eq!(whazzup.clients.observers.len(), 1);
+
+        let pilot = whazzup.pilot("BAW123").unwrap();
+        let track = pilot.last_track.as_ref().unwrap();
+        let (x, y) = geo_to_map(52.4, -8.1);
+        assert_abs_diff_eq!(track.map_x, x);
+        assert_abs_diff_eq!(track.map_y, y);
+        assert_eq!(track.transponder, 2000);
+
+        let flight_plan = pilot.flight_plan.as_ref().unwrap();
+        assert_eq!(flight_plan.arrival_id.as_deref(), Some("KJFK"));
+        assert_eq!(flight_plan.alternative2_id, None);
+        assert_eq!(flight_plan.aircraft.as_ref().unwrap().wake_turbulence.as_deref(), Some("H"));
+
+        let pilot = whazzup.pilot("DLH4AB").unwrap();
+        assert!(pilot.last_track.is_none());
+        assert!(pilot.flight_plan.is_none());
+
+        let atc = whazzup.atc("EGLL_TWR").unwrap();
+        assert_abs_diff_eq!(atc.atc_session.as_ref().unwrap().frequency, 118.7);
+        assert_eq!(atc.atis.as_ref().unwrap().lines.len(), 3);
+    }
+
+    #[test]
+    fn test_serialize_map_position() {
+        let whazzup = Whazzup::parse(FIXTURE).unwrap();
+        let json = serde_json::to_value(&whazzup).unwrap();
+        let track = &json["clients"]["pilots"][0]["lastTrack"];
+        assert!(track["mapX"].is_f64());
+        assert!(track["mapY"].is_f64());
+
+        let reparsed: Whazzup = serde_json::from_value(json).unwrap();
+        assert_eq!(reparsed, whazzup);
+    }
+}